@@ -1,162 +1,880 @@
 //! The role of the intermediate is to turn the ast into bytecode.
-use crate::bytecode::Opcode::Constant;
-use crate::compiler::Compiler;
-use crate::parser::{Expr, Object, Statement};
+use crate::compiler::{Compiler, StructConstraint, Upvalue};
+use crate::parser::{
+    Arm, BuiltinType, Constraint, Expr, Field, FieldDeclaration, Kind, Object, Pattern,
+    QualifiedName, Statement, Type,
+};
+use crate::prelude;
+use crate::token::Token;
+use crate::trace::{Phase, Tracer};
 use crate::TokenType;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug)]
 pub enum Opcode {
     Pop,
     Nil,
     /// From locals to the stack
-    Load(u8),
+    Load(u32),
     /// From the stack to the locals
-    Store(u8),
+    Store(u32),
     Constant(u32),
     Struct(u32),
     Array(u32),
+    /// Pops key and value pairs into a map
+    Map(u32),
+    /// Pops a value then an index, pushes the item or nil
+    Index,
+    /// Pushes the next value then key of the collection in the local, using
+    /// the following local as cursor. Jumps once the collection is exhausted.
+    Next(u32, u32),
+    /// Pops a value and pushes it to the array in the local
+    Append(u32),
+    /// From the captured values of the running function to the stack
+    LoadUpvalue(u32),
+    /// Copies the function constant, capturing its upvalues
+    Closure(u32),
     Call,
+    /// Wraps the top of the stack in the variant named by the constant
+    Variant(u32),
+    /// Replaces a variant by its payload
+    Unwrap,
+    Jump(u32),
+    /// Jumps if the top of the stack is false, leaving it there
+    JumpIfFalse(u32),
+    /// Jumps if the top of the stack is true, leaving it there
+    JumpIfTrue(u32),
+    /// Pops a value and jumps unless it is the variant named by the constant
+    JumpIfNotVariant(u32, u32),
+    /// Pops a value and jumps unless its kind is named by the constant
+    JumpIfNotKind(u32, u32),
+    /// Copies the struct on top of the stack, overridden by the struct below it
+    Merge,
+    /// Same as `Merge`, recursing into the structs present on both sides
+    DeepMerge,
+    /// Same as `Merge`, updating the nested structs named by the shape
+    /// constant instead of replacing them
+    Update(u32),
     Add,
     Multiply,
+    Negate,
+    Not,
+    Equal,
+    Less,
+    Greater,
+    /// Pops a predicate, and calls it with the field at the path constant of
+    /// the struct on top of the stack. Raises `ConstraintFailed` with the
+    /// message constant and the field value when it does not hold.
+    Check(u32, u32),
+    /// Pops a condition then a message, raises `AssertionFailed` with the
+    /// message constant followed by the message when the condition is false.
+    Assert(u32),
     Return,
 }
 
+/// Compiled function, shared by the closures created from it and the frames
+/// running them.
+#[derive(Debug)]
+pub struct Prototype {
+    pub arity: u8,
+    pub name: String,
+    pub chunk: Chunk,
+    pub ty: Type,
+}
+
+/// Source position of the token an instruction was compiled from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Span {
+    fn of(token: &Token) -> Span {
+        Span {
+            line: token.line,
+            col: token.col,
+        }
+    }
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "hello.m:{}:{}", self.line, self.col)
+    }
+}
+
+/// Span of each instruction of a chunk, run-length encoded: an expression
+/// usually compiles to several instructions in a row sharing its span.
+#[derive(Clone, Debug, Default)]
+pub struct LineTable {
+    /// Spans with the number of consecutive instructions they cover.
+    runs: Vec<(Span, u32)>,
+}
+
+impl LineTable {
+    fn push(&mut self, span: Span) {
+        match self.runs.last_mut() {
+            Some((last, count)) if *last == span => *count += 1,
+            _ => self.runs.push((span, 1)),
+        }
+    }
+
+    /// Span of the instruction at `index`.
+    pub fn get(&self, index: usize) -> Option<Span> {
+        let mut start = 0;
+        for (span, count) in &self.runs {
+            start += *count as usize;
+            if index < start {
+                return Some(*span);
+            }
+        }
+        None
+    }
+}
+
+/// Constants a chunk loads more than once share a slot. Numbers are compared
+/// by their bits, so that `0` and `-0` keep their own.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum ConstantKey {
+    Num(u64),
+    String(Rc<str>),
+    Bool(bool),
+    Variant(Rc<str>),
+    Native(&'static str),
+    Nil,
+}
+
+impl ConstantKey {
+    /// None for values that are not shared: functions, collections and
+    /// variants with a payload.
+    fn of(value: &Object) -> Option<ConstantKey> {
+        match value {
+            Object::Num(n) => Some(ConstantKey::Num(n.to_bits())),
+            Object::String(s) => Some(ConstantKey::String(s.clone())),
+            Object::Bool(b) => Some(ConstantKey::Bool(*b)),
+            Object::Variant(tag, None) => Some(ConstantKey::Variant(tag.clone())),
+            Object::NativeFunction(native) => Some(ConstantKey::Native(native.name)),
+            Object::Nil => Some(ConstantKey::Nil),
+            _ => None,
+        }
+    }
+}
+
+/// Strings of the constants of a program and its functions. Field names and
+/// tags are allocated once, structs built at runtime share them.
+type Strings = Rc<RefCell<HashSet<Rc<str>>>>;
+
 #[derive(Clone, Debug)]
 pub struct Chunk {
     compiler: Compiler, // todo change to the opposite dependency between compiler and chunk
     pub code: Vec<Opcode>,
+    pub spans: LineTable,
     pub constants: Vec<Object>,
-    debug: bool,
+    /// Index of the shared constants.
+    shared: HashMap<ConstantKey, u32>,
+    strings: Strings,
+    pub upvalues: Vec<Upvalue>,
+    /// Span of the statement or expression being compiled.
+    span: Span,
+    tracer: Tracer,
 }
 
 impl Chunk {
-    pub fn init(debug: bool) -> Self {
+    pub fn init(tracer: Tracer) -> Self {
         Chunk {
-            compiler: Compiler::init(debug),
+            compiler: Compiler::init(tracer.clone()),
             code: vec![],
+            spans: LineTable::default(),
             constants: vec![],
-            debug,
+            shared: HashMap::new(),
+            strings: Strings::default(),
+            upvalues: vec![],
+            span: Span::default(),
+            tracer,
         }
     }
 
-    fn enclosed_chunk(&self) -> Chunk {
-        Chunk {
-            compiler: self.compiler.clone(),
+    /// Compiles a function body with `compile_body`, and emits the closure
+    /// creating it. The frame holds the function itself after the arguments,
+    /// in a local named `callee`: empty when the body cannot refer to it.
+    fn function(
+        &mut self,
+        name: &str,
+        params: &[Token],
+        callee: &str,
+        ty: Type,
+        compile_body: impl FnOnce(&mut Chunk),
+    ) {
+        let enclosing = std::mem::replace(&mut self.compiler, Compiler::init(self.tracer.clone()));
+        let mut fn_chunk = Chunk {
+            compiler: enclosing.enclosed(),
             code: vec![],
+            spans: LineTable::default(),
             constants: vec![],
-            debug: self.debug,
+            shared: HashMap::new(),
+            strings: self.strings.clone(),
+            upvalues: vec![],
+            span: self.span,
+            tracer: self.tracer.clone(),
+        };
+        for param in params {
+            fn_chunk.compiler.add_variable(param);
         }
+        fn_chunk.compiler.add_local(callee);
+        compile_body(&mut fn_chunk);
+        // Functions returning `Unit` may end without a return.
+        fn_chunk.emit(Opcode::Nil);
+        fn_chunk.emit(Opcode::Return);
+        fn_chunk.upvalues = fn_chunk.compiler.upvalues.clone();
+        self.compiler = fn_chunk.compiler.end();
+
+        let prototype = Prototype {
+            arity: self.operand(params.len(), "parameters"),
+            name: name.to_string(),
+            chunk: fn_chunk,
+            ty,
+        };
+        let i = self.add_constant(Object::Function(Rc::new(prototype), Rc::default()));
+        self.emit(Opcode::Closure(i));
     }
 
+    /// Compiles a script, it returns `main` or null when it has none.
     pub fn compile(&mut self, body: &[Statement]) {
-        for stmt in body {
-            if self.debug {
-                println!("[Chunk] compiling stmt: {:?}", stmt);
-            }
-            self.statement(stmt);
+        self.statements(body);
+        let has_main = body.iter().any(
+            |stmt| matches!(stmt, Statement::Variable(true, name, _, _) if name.lexeme == "main"),
+        );
+        if !has_main {
+            self.emit(Opcode::Nil);
+            self.emit(Opcode::Return);
         }
-        if self.debug {
-            println!("[Chunk] compiled following bytecode:");
-            for line in &self.code {
-                println!("[Chunk] {:?}", line);
+        if self.tracer.enabled(Phase::Compiler) {
+            for (offset, op) in self.code.iter().enumerate() {
+                self.tracer.event(Phase::Compiler, "instruction", || {
+                    vec![
+                        ("offset", offset.to_string()),
+                        (
+                            "span",
+                            self.spans.get(offset).unwrap_or_default().to_string(),
+                        ),
+                        ("op", format!("{:?}", op)),
+                    ]
+                });
             }
         }
     }
 
+    fn statements(&mut self, body: &[Statement]) {
+        for stmt in body {
+            self.tracer.event(Phase::Compiler, "statement", || {
+                vec![("statement", format!("{:?}", stmt))]
+            });
+            self.statement(stmt);
+        }
+    }
+
+    fn emit(&mut self, op: Opcode) {
+        self.code.push(op);
+        self.spans.push(self.span);
+    }
+
     fn statement(&mut self, stmt: &Statement) {
         match stmt {
-            Statement::Variable(exported, name, opt_val) => {
+            Statement::Variable(_, token, _, _)
+            | Statement::Return(token, _)
+            | Statement::Struct(_, token, _, _, _)
+            | Statement::Assert(token, _, _)
+            | Statement::Fn(_, token, _, _, _, _) => self.span = Span::of(token),
+            Statement::Expr(expr) => {
+                if let Some(token) = expr.token() {
+                    self.span = Span::of(token);
+                }
+            }
+            Statement::Enum(_, _, _) | Statement::Type(_, _, _) | Statement::Import(_, _) => {}
+        }
+        match stmt {
+            Statement::Variable(exported, name, _, opt_val) => {
                 if let Some(val) = opt_val {
                     self.expression(val);
                 } else {
-                    self.code.push(Opcode::Nil);
+                    self.emit(Opcode::Nil);
                 }
 
                 if *exported && "main" == name.lexeme {
-                    self.code.push(Opcode::Return);
+                    self.emit(Opcode::Return);
                 } else {
                     let i = self.compiler.add_variable(name);
-                    self.code.push(Opcode::Store(i as u8));
+                    self.emit(Opcode::Store(self.slot(i)));
                 }
             }
-            Statement::Return(expr) => {
+            Statement::Return(_, expr) => {
                 self.expression(expr);
-                self.code.push(Opcode::Return);
+                self.emit(Opcode::Return);
             }
             Statement::Expr(expr) => {
                 self.expression(expr);
-                self.code.push(Opcode::Pop);
+                self.emit(Opcode::Pop);
             }
-            Statement::Struct(_, _, _) => {}
-            Statement::Import(_, _) => {}
-            Statement::Fn(_, name, args, ret_ty, body) => {
-                let mut fn_chunk = self.enclosed_chunk();
-                for (arg, _ty) in args {
-                    fn_chunk.compiler.add_variable(arg);
+            Statement::Struct(_, name, _, fields, constraints) => {
+                self.compiler.add_struct(name, fields);
+                for (i, constraint) in constraints.iter().enumerate() {
+                    self.constraint(name, i, constraint);
+                }
+            }
+            Statement::Assert(token, condition, message) => {
+                if let Some(message) = message {
+                    self.expression(message);
+                } else {
+                    self.emit(Opcode::Nil);
                 }
-                fn_chunk.compile(body);
+                self.expression(condition);
+                let i = self.add_string(&format!("hello.m:{}: Assertion failed", token.line));
+                self.emit(Opcode::Assert(i));
+            }
+            Statement::Enum(_, _, _) => {}
+            Statement::Type(_, name, ty) => self.compiler.add_alias(name, ty),
+            Statement::Import(_, _) => {}
+            Statement::Fn(_, name, _, args, ret_ty, body) => {
+                let params: Vec<Token> = args.iter().map(|a| a.0).collect();
+                let ty = Type::Fn(
+                    args.iter().map(|a| a.1.clone()).collect(),
+                    Box::new(ret_ty.clone()),
+                );
+                self.function(name.lexeme, &params, name.lexeme, ty, |chunk| {
+                    chunk.statements(body)
+                });
                 let i = self.compiler.add_variable(name);
-                self.constants.push(Object::Function(
-                    args.len() as u8,
-                    name.lexeme.to_string(),
-                    fn_chunk,
-                    ret_ty.clone(),
-                ));
-                self.code.push(Constant(self.constants.len() as u32 - 1));
-                self.code.push(Opcode::Store(i as u8))
+                self.emit(Opcode::Store(self.slot(i)))
             }
         }
     }
 
+    /// Instructions are located at the innermost expression with a token.
     fn expression(&mut self, expr: &Expr) {
+        let span = self.span;
+        if let Some(token) = expr.token() {
+            self.span = Span::of(token);
+        }
+        self.expression_at(expr);
+        self.span = span;
+    }
+
+    fn expression_at(&mut self, expr: &Expr) {
         match expr {
-            Expr::Call(target, args) => {
+            Expr::Call(target, _, args) => {
                 for arg in args {
                     self.expression(arg);
                 }
                 self.expression(target);
-                self.code.push(Opcode::Call);
+                self.emit(Opcode::Call);
+            }
+            Expr::Binary(left, op, right)
+                if op.kind == TokenType::AndAnd || op.kind == TokenType::PipePipe =>
+            {
+                // The right operand is skipped when the left one decides.
+                self.expression(left);
+                if op.kind == TokenType::AndAnd {
+                    self.emit(Opcode::JumpIfFalse(0));
+                } else {
+                    self.emit(Opcode::JumpIfTrue(0));
+                }
+                let end = self.code.len() - 1;
+                self.emit(Opcode::Pop);
+                self.expression(right);
+                self.patch_jump(end);
             }
             Expr::Binary(left, op, right) => {
                 self.expression(right);
                 self.expression(left);
                 match op.kind {
-                    TokenType::Plus => self.code.push(Opcode::Add),
-                    TokenType::Star => self.code.push(Opcode::Multiply),
+                    TokenType::Plus => self.emit(Opcode::Add),
+                    TokenType::Star => self.emit(Opcode::Multiply),
+                    TokenType::SlashSlash => self.emit(Opcode::Merge),
+                    TokenType::SlashBackslash => self.emit(Opcode::DeepMerge),
+                    TokenType::EqualEqual => self.emit(Opcode::Equal),
+                    TokenType::BangEqual => {
+                        self.emit(Opcode::Equal);
+                        self.emit(Opcode::Not);
+                    }
+                    TokenType::LeftCaret => self.emit(Opcode::Less),
+                    TokenType::RightCaret => self.emit(Opcode::Greater),
+                    TokenType::LessEqual => {
+                        self.emit(Opcode::Greater);
+                        self.emit(Opcode::Not);
+                    }
+                    TokenType::GreaterEqual => {
+                        self.emit(Opcode::Less);
+                        self.emit(Opcode::Not);
+                    }
                     _ => {}
                 }
             }
-            Expr::Struct(_name, fields) => {
-                for f in fields.iter().rev() {
-                    self.expression(&f.1);
-                    self.constants.push(Object::String(f.0.lexeme.to_string()));
-                    self.code
-                        .push(Opcode::Constant(self.constants.len() as u32 - 1))
+            Expr::Unary(op, value) => {
+                self.expression(value);
+                if op.kind == TokenType::Bang {
+                    self.emit(Opcode::Not);
+                } else {
+                    self.emit(Opcode::Negate);
+                }
+            }
+            Expr::Struct(name, Some(base), fields) => {
+                self.fields(fields);
+                self.expression(base);
+                match shape(fields) {
+                    Some(shape) => {
+                        let i = self.add_constant(shape);
+                        self.emit(Opcode::Update(i));
+                    }
+                    None => self.emit(Opcode::Merge),
                 }
-                self.code.push(Opcode::Struct(fields.len() as u32));
+                self.check_constraints(name.lexeme, fields, name.line);
+            }
+            Expr::Struct(name, None, fields) => {
+                let declarations = self
+                    .compiler
+                    .resolve_struct(&QualifiedName(name.lexeme.to_string()));
+                self.struct_fields(&declarations.unwrap_or_default(), fields);
+                self.check_constraints(name.lexeme, fields, name.line);
+            }
+            Expr::Anonymous(fields) => self.fields(fields),
+            Expr::Variant(_, variant, None) => {
+                let i = self.add_constant(Object::Variant(self.intern(variant.lexeme), None));
+                self.emit(Opcode::Constant(i));
+            }
+            Expr::Variant(_, variant, Some(payload)) => {
+                self.expression(payload);
+                let i = self.add_string(variant.lexeme);
+                self.emit(Opcode::Variant(i));
+            }
+            Expr::Match(_, value, arms) => self.match_arms(value, arms),
+            Expr::Lambda(params, body) => {
+                let names: Vec<Token> = params.iter().map(|p| p.0).collect();
+                let ty = Type::Fn(
+                    params
+                        .iter()
+                        .map(|p| p.1.clone().unwrap_or(Type::Infer))
+                        .collect(),
+                    Box::new(Type::Infer),
+                );
+                self.function("lambda", &names, "", ty, |chunk| {
+                    chunk.expression(body);
+                    chunk.emit(Opcode::Return);
+                });
+            }
+            Expr::Map(_, entries) => {
+                for (key, value) in entries.iter().rev() {
+                    self.expression(value);
+                    self.expression(key);
+                }
+                let len = self.operand(entries.len(), "map entries");
+                self.emit(Opcode::Map(len));
+            }
+            Expr::Index(value, _, index) => {
+                self.expression(index);
+                self.expression(value);
+                self.emit(Opcode::Index);
+            }
+            Expr::For(_, key, value, iterable, body) => {
+                self.comprehension(key, value, iterable, body)
             }
             Expr::Grouping(expr) => {
                 self.expression(expr);
             }
-            Expr::Array(values) => {
+            Expr::Array(_, values) => {
                 for val in values.iter().rev() {
                     self.expression(val);
                 }
-                self.code.push(Opcode::Array(values.len() as u32));
+                let len = self.operand(values.len(), "array items");
+                self.emit(Opcode::Array(len));
             }
-            Expr::Literal(lit) => {
-                self.constants.push(lit.clone());
-                self.code
-                    .push(Opcode::Constant(self.constants.len() as u32 - 1));
+            Expr::Literal(_, lit) => {
+                let i = self.add_constant(lit.clone());
+                self.emit(Opcode::Constant(i));
             }
-            Expr::Variable(name) => {
-                if let Some(i) = self.compiler.resolve_variable(name) {
-                    self.code.push(Opcode::Load(i as u8));
-                } else {
-                    eprintln!("Could not resolve variable name '{}'.", name.lexeme);
-                    std::process::exit(12);
+            Expr::Variable(name) => self.variable(name),
+        }
+    }
+
+    fn variable(&mut self, name: &Token) {
+        if let Some(i) = self.compiler.resolve_variable(name) {
+            self.emit(Opcode::Load(self.slot(i)));
+        } else if let Some(i) = self.compiler.resolve_upvalue(name) {
+            self.emit(Opcode::LoadUpvalue(self.operand(i, "captured variables")));
+        } else if let Some(native) = prelude::find(name.lexeme) {
+            let i = self.add_constant(Object::NativeFunction(native));
+            self.emit(Opcode::Constant(i));
+        } else {
+            eprintln!("Could not resolve variable name '{}'.", name.lexeme);
+            std::process::exit(12);
+        }
+    }
+
+    /// Compiles the constraint into a predicate on `it`, kept in a local that
+    /// no identifier can name.
+    fn constraint(&mut self, name: &Token, index: usize, constraint: &Constraint) {
+        let description = self.predicate(constraint);
+
+        let predicate = format!("{}#{}", name.lexeme, index);
+        let i = self.compiler.add_local(&predicate);
+        self.emit(Opcode::Store(self.slot(i)));
+        self.compiler.add_constraint(StructConstraint {
+            name: QualifiedName(name.lexeme.to_string()),
+            path: constraint.path().to_vec(),
+            predicate,
+            description,
+        });
+    }
+
+    /// Emits the closure of the predicate, and gives the description of the
+    /// values it rejects.
+    fn predicate(&mut self, constraint: &Constraint) -> String {
+        let token = constraint.token();
+        self.span = Span::of(&token);
+        let it = Token {
+            kind: TokenType::Identifier,
+            lexeme: "it",
+            ..token
+        };
+        let ty = Type::Fn(
+            vec![Type::Infer],
+            Box::new(Type::Builtin(BuiltinType::Bool)),
+        );
+        match constraint {
+            Constraint::Where(_, _, condition) => {
+                self.function("where", &[it], "", ty, |chunk| {
+                    chunk.expression(condition);
+                    chunk.emit(Opcode::Return);
+                });
+                format!("does not satisfy the constraint at line {}", token.line)
+            }
+            Constraint::Matching(_, _, pattern) => {
+                self.function("matching", &[it], "", ty, |chunk| {
+                    chunk.variable(&it);
+                    let i = chunk.add_string(pattern);
+                    chunk.emit(Opcode::Constant(i));
+                    let i = chunk.add_constant(Object::NativeFunction(
+                        prelude::find("matches").expect("matches is a native"),
+                    ));
+                    chunk.emit(Opcode::Constant(i));
+                    chunk.emit(Opcode::Call);
+                    chunk.emit(Opcode::Return);
+                });
+                format!("does not match '{}'", pattern)
+            }
+            Constraint::Integer(_, _) => {
+                self.function("integer", &[it], "", ty, |chunk| {
+                    chunk.variable(&it);
+                    let i = chunk.add_constant(Object::NativeFunction(
+                        prelude::find("isInteger").expect("isInteger is a native"),
+                    ));
+                    chunk.emit(Opcode::Constant(i));
+                    chunk.emit(Opcode::Call);
+                    chunk.emit(Opcode::Return);
+                });
+                "is not an integer".to_string()
+            }
+            Constraint::Imported(origin, constraint) => {
+                self.predicate(constraint);
+                format!("does not satisfy the schema at {}", origin)
+            }
+        }
+    }
+
+    /// Checks the constraints of the struct `name` on top of the stack. Errors
+    /// point at the field in the literal, or at the literal itself for fields
+    /// it does not set.
+    fn check_constraints(&mut self, name: &str, fields: &[Field], line: usize) {
+        for constraint in self
+            .compiler
+            .resolve_constraints(&QualifiedName(name.to_string()))
+        {
+            let line = fields
+                .iter()
+                .find(|f| f.0.lexeme == constraint.path[0].0)
+                .map(|f| f.0.line)
+                .unwrap_or(line);
+            self.variable(&Token {
+                kind: TokenType::Identifier,
+                lexeme: &constraint.predicate,
+                line,
+                col: 0,
+                stack_offset: 0,
+            });
+            let path: Vec<&str> = constraint.path.iter().map(|p| &*p.0).collect();
+            let message = format!(
+                "hello.m:{}: Field '{}' of '{}' {}",
+                line,
+                path.join("."),
+                name,
+                constraint.description
+            );
+            let path: Vec<Object> = path
+                .iter()
+                .map(|p| Object::String(self.intern(p)))
+                .collect();
+            let path = Object::Array(path.into());
+            let path = self.add_constant(path);
+            let message = self.add_string(&message);
+            self.emit(Opcode::Check(path, message));
+        }
+    }
+
+    /// The matched value is kept in a temporary local, each arm tests it and
+    /// jumps to the next arm on mismatch.
+    fn match_arms(&mut self, value: &Expr, arms: &[Arm]) {
+        self.expression(value);
+        let scope = self.compiler.begin_scope();
+        let value_slot = self.temporary();
+        self.emit(Opcode::Store(value_slot));
+
+        let mut exits = vec![];
+        for Arm(pattern, expr) in arms {
+            let arm_scope = self.compiler.begin_scope();
+            let next_arm = match pattern {
+                Pattern::Variant(name, binding) => {
+                    self.emit(Opcode::Load(value_slot));
+                    let tag = self.add_string(name.lexeme);
+                    self.emit(Opcode::JumpIfNotVariant(tag, 0));
+                    let next_arm = self.code.len() - 1;
+                    if let Some(binding) = binding {
+                        self.emit(Opcode::Load(value_slot));
+                        self.emit(Opcode::Unwrap);
+                        let i = self.compiler.add_variable(binding);
+                        self.emit(Opcode::Store(self.slot(i)));
+                    }
+                    Some(next_arm)
+                }
+                Pattern::Type(binding, ty) => {
+                    self.emit(Opcode::Load(value_slot));
+                    let kind = self.add_string(self.kind(ty).name());
+                    self.emit(Opcode::JumpIfNotKind(kind, 0));
+                    let next_arm = self.code.len() - 1;
+                    if binding.lexeme != "_" {
+                        self.emit(Opcode::Load(value_slot));
+                        let i = self.compiler.add_variable(binding);
+                        self.emit(Opcode::Store(self.slot(i)));
+                    }
+                    Some(next_arm)
                 }
+                Pattern::Wildcard(_) => None,
+            };
+
+            self.expression(expr);
+            self.emit(Opcode::Jump(0));
+            exits.push(self.code.len() - 1);
+            if let Some(next_arm) = next_arm {
+                self.patch_jump(next_arm);
             }
+            self.compiler.end_scope(arm_scope);
+        }
+
+        for exit in exits {
+            self.patch_jump(exit);
+        }
+        self.compiler.end_scope(scope);
+    }
+
+    /// The iterated collection, its cursor and the resulting array live in
+    /// temporary locals for the duration of the loop.
+    fn comprehension(&mut self, key: &Option<Token>, value: &Token, iterable: &Expr, body: &Expr) {
+        self.expression(iterable);
+        let scope = self.compiler.begin_scope();
+        let collection = self.temporary();
+        self.emit(Opcode::Store(collection));
+        let cursor = self.temporary();
+        let i = self.add_constant(Object::Num(0.0));
+        self.emit(Opcode::Constant(i));
+        self.emit(Opcode::Store(cursor));
+        let result = self.temporary();
+        self.emit(Opcode::Array(0));
+        self.emit(Opcode::Store(result));
+
+        let start = self.operand(self.code.len(), "instructions");
+        self.emit(Opcode::Next(collection, 0));
+        let exit = self.code.len() - 1;
+        if let Some(key) = key {
+            let i = self.compiler.add_variable(key);
+            self.emit(Opcode::Store(self.slot(i)));
+        } else {
+            self.emit(Opcode::Pop);
         }
+        let i = self.compiler.add_variable(value);
+        self.emit(Opcode::Store(self.slot(i)));
+
+        self.expression(body);
+        self.emit(Opcode::Append(result));
+        self.emit(Opcode::Jump(start));
+        self.patch_jump(exit);
+
+        self.emit(Opcode::Load(result));
+        self.compiler.end_scope(scope);
+    }
+
+    /// Points the jump at `offset` to the next instruction to be emitted.
+    fn patch_jump(&mut self, offset: usize) {
+        let target = self.operand(self.code.len(), "instructions");
+        match &mut self.code[offset] {
+            Opcode::Jump(t)
+            | Opcode::JumpIfFalse(t)
+            | Opcode::JumpIfTrue(t)
+            | Opcode::JumpIfNotVariant(_, t)
+            | Opcode::JumpIfNotKind(_, t)
+            | Opcode::Next(_, t) => *t = target,
+            op => panic!("Cannot patch {:?}, it is not a jump", op),
+        }
+    }
+
+    /// Same as the type checker's `kind`, names that are neither structs nor
+    /// aliases are enums.
+    fn kind(&self, ty: &Type) -> Kind {
+        ty.kind(&|name| {
+            if let Some(alias) = self.compiler.resolve_alias(name) {
+                Some(self.kind(&alias))
+            } else if self.compiler.resolve_struct(name).is_some() {
+                Some(Kind::Struct)
+            } else {
+                Some(Kind::Variant)
+            }
+        })
+        .unwrap_or(Kind::Variant)
+    }
+
+    /// Index of the value in the constants, reusing the slot of an equal
+    /// literal. Strings are interned.
+    fn add_constant(&mut self, value: Object) -> u32 {
+        let value = match value {
+            Object::String(s) => Object::String(self.intern(&s)),
+            value => value,
+        };
+        let key = ConstantKey::of(&value);
+        if let Some(i) = key.as_ref().and_then(|key| self.shared.get(key)) {
+            return *i;
+        }
+        self.constants.push(value);
+        let i = self.operand(self.constants.len() - 1, "constants");
+        if let Some(key) = key {
+            self.shared.insert(key, i);
+        }
+        i
+    }
+
+    fn add_string(&mut self, s: &str) -> u32 {
+        let s = self.intern(s);
+        self.add_constant(Object::String(s))
+    }
+
+    fn intern(&self, s: &str) -> Rc<str> {
+        let mut strings = self.strings.borrow_mut();
+        if let Some(interned) = strings.get(s) {
+            return interned.clone();
+        }
+        let interned: Rc<str> = s.into();
+        strings.insert(interned.clone());
+        interned
+    }
+
+    fn slot(&self, index: usize) -> u32 {
+        self.operand(index, "local variables")
+    }
+
+    /// Slot of a new local no identifier can name.
+    fn temporary(&mut self) -> u32 {
+        let i = self.compiler.add_local("");
+        self.slot(i)
+    }
+
+    /// Converts a slot, index or count to its operand type, exiting with a
+    /// compile error when it does not fit rather than truncating it.
+    fn operand<T: std::convert::TryFrom<usize>>(&self, value: usize, what: &str) -> T {
+        T::try_from(value).unwrap_or_else(|_| {
+            eprintln!("{}: Too many {} in one function.", self.span, what);
+            std::process::exit(12)
+        })
+    }
+
+    fn fields(&mut self, fields: &[Field]) {
+        for f in fields.iter().rev() {
+            self.expression(&f.1);
+            self.field_name(f.0.lexeme);
+        }
+        let len = self.operand(fields.len(), "fields");
+        self.emit(Opcode::Struct(len));
+    }
+
+    /// Builds a struct in declaration order, omitted fields take their
+    /// default value.
+    fn struct_fields(&mut self, declarations: &[FieldDeclaration], fields: &[Field]) {
+        for declaration in declarations.iter().rev() {
+            match fields.iter().find(|f| f.0.lexeme == declaration.0 .0) {
+                Some(Field(token, Expr::Anonymous(nested))) => {
+                    let nested_declarations = self.struct_declaration(&declaration.1);
+                    self.struct_fields(&nested_declarations.unwrap_or_default(), nested);
+                    self.check_nested_constraints(&declaration.1, nested, token.line);
+                }
+                Some(f) => self.expression(&f.1),
+                None => self.default(declaration),
+            }
+            self.field_name(&declaration.0 .0);
+        }
+        let len = self.operand(declarations.len(), "fields");
+        self.emit(Opcode::Struct(len));
+    }
+
+    fn default(&mut self, declaration: &FieldDeclaration) {
+        if let Some(value) = &declaration.2 {
+            let i = self.add_constant(value.clone());
+            self.emit(Opcode::Constant(i));
+        } else if let Some(nested) = self.struct_declaration(&declaration.1) {
+            self.struct_fields(&nested, &[]);
+        } else {
+            self.emit(Opcode::Nil);
+        }
+    }
+
+    /// Nested structs built from dotted paths are checked against the
+    /// constraints of their declared struct.
+    fn check_nested_constraints(&mut self, ty: &Type, fields: &[Field], line: usize) {
+        match ty {
+            Type::Explicit(name) => self.check_constraints(&name.0.clone(), fields, line),
+            Type::Nested(base, _) => self.check_nested_constraints(base, fields, line),
+            _ => {}
+        }
+    }
+
+    fn struct_declaration(&self, ty: &Type) -> Option<Vec<FieldDeclaration>> {
+        match ty {
+            Type::Struct(declarations) => Some(declarations.clone()),
+            Type::Explicit(name) => self.compiler.resolve_struct(name),
+            Type::Nested(base, _) => self.struct_declaration(base),
+            _ => None,
+        }
+    }
+
+    fn field_name(&mut self, name: &str) {
+        let i = self.add_string(name);
+        self.emit(Opcode::Constant(i));
+    }
+}
+
+/// Nested structs built from dotted paths in a `..base` literal, which update
+/// the fields of the base instead of replacing them. None without any.
+fn shape(fields: &[Field]) -> Option<Object> {
+    let nested: Vec<(Rc<str>, Object)> = fields
+        .iter()
+        .filter_map(|f| match &f.1 {
+            Expr::Anonymous(nested) => Some((
+                f.0.lexeme.into(),
+                shape(nested).unwrap_or_else(|| Object::Struct(Rc::new(vec![]))),
+            )),
+            _ => None,
+        })
+        .collect();
+    if nested.is_empty() {
+        None
+    } else {
+        Some(Object::Struct(nested.into()))
     }
 }
@@ -0,0 +1,530 @@
+//! Type definitions in other languages, for programs reading Mia output.
+//!
+//! `rust` backs `mia rust` and build scripts. Structs, enums and aliases
+//! become Rust declarations deriving `serde::Deserialize`, so they read what
+//! the formatter writes. Nested structs and unions get a declaration of their
+//! own, named after their parent and field. Function fields are not data and
+//! are skipped with a comment.
+//!
+//! `typescript` backs `mia typescript`, declaring interfaces for structs,
+//! types for enums and aliases, and constants for exported values. TypeScript
+//! types are structural, so nested structs are written inline.
+use crate::formatter::EnumEncoding;
+use crate::parser::{
+    BuiltinType, FieldDeclaration, Program, QualifiedName, Statement, Type, VariantDeclaration,
+};
+use crate::type_checker::TypeChecker;
+
+const DERIVE: &str = "#[derive(Debug, Clone, PartialEq, serde::Deserialize)]";
+
+/// Rust declarations for the structs, enums and aliases of a module.
+pub fn rust(program: &Program, source: &str, enum_encoding: EnumEncoding) -> String {
+    let mut generator = RustGenerator {
+        enum_encoding,
+        declarations: vec![],
+    };
+    for statement in &program.0 {
+        match statement {
+            Statement::Struct(_, name, params, fields, _) => {
+                generator.structure(&type_name(name.lexeme), &generics(params), fields)
+            }
+            Statement::Enum(_, name, variants) => {
+                generator.enumeration(&type_name(name.lexeme), variants)
+            }
+            Statement::Type(_, name, Type::Generic(params, ty)) => {
+                generator.alias(&type_name(name.lexeme), &generics(params), ty)
+            }
+            Statement::Type(_, name, ty) => generator.alias(&type_name(name.lexeme), "", ty),
+            _ => {}
+        }
+    }
+
+    let mut out = format!("// Generated by mia from {}, do not edit.\n", source);
+    for declaration in generator.declarations {
+        out.push('\n');
+        out.push_str(&declaration);
+    }
+    out
+}
+
+struct RustGenerator {
+    enum_encoding: EnumEncoding,
+    declarations: Vec<String>,
+}
+
+impl RustGenerator {
+    fn structure(&mut self, name: &str, generics: &str, fields: &[FieldDeclaration]) {
+        let mut body = String::new();
+        for FieldDeclaration(field, ty, _) in fields {
+            let nested = format!("{}{}", name, pascal_case(&field.0));
+            match self.ty(ty, &nested, generics) {
+                Some(ty) => {
+                    let identifier = identifier(&snake_case(&field.0));
+                    if identifier.trim_start_matches("r#") != field.0 {
+                        body.push_str(&format!("    #[serde(rename = {:?})]\n", field.0));
+                    }
+                    body.push_str(&format!("    pub {}: {},\n", identifier, ty));
+                }
+                None => body.push_str(&format!(
+                    "    // '{}' is skipped, '{}' is not data.\n",
+                    field.0,
+                    ty.print()
+                )),
+            }
+        }
+        self.declarations.push(format!(
+            "{}\npub struct {}{} {{\n{}}}\n",
+            DERIVE, name, generics, body
+        ));
+    }
+
+    /// Read as the formatter writes variants with `enum_encoding`. Tagged
+    /// variants map to adjacently tagged enums, and enums without payloads to
+    /// plain enums. Otherwise unit variants are strings next to tagged or bare
+    /// payloads, which no serde attribute reads, so the enum is read through
+    /// an untagged `{name}Repr` enum.
+    fn enumeration(&mut self, name: &str, variants: &[VariantDeclaration]) {
+        let payloads = variants.iter().any(|v| v.1.is_some());
+        let mut units = vec![];
+        let mut tagged = vec![];
+        let mut body = String::new();
+        for VariantDeclaration(variant, payload) in variants {
+            let payload = payload.as_ref().map(|payload| {
+                let nested = format!("{}{}", name, variant.0);
+                self.ty(payload, &nested, "")
+            });
+            match payload {
+                Some(Some(payload)) => {
+                    body.push_str(&format!("    {}({}),\n", variant.0, payload));
+                    tagged.push((&variant.0, payload));
+                }
+                Some(None) => body.push_str(&format!(
+                    "    // '{}' is skipped, its payload is not data.\n",
+                    variant.0
+                )),
+                None => {
+                    body.push_str(&format!("    {},\n", variant.0));
+                    units.push(&variant.0);
+                }
+            }
+        }
+
+        let attribute = match (self.enum_encoding, payloads) {
+            (EnumEncoding::Tagged, _) => {
+                "#[serde(tag = \"tag\", content = \"value\")]\n".to_string()
+            }
+            (_, false) => String::new(),
+            (_, true) => format!("#[serde(from = \"{}Repr\")]\n", name),
+        };
+        self.declarations.push(format!(
+            "{}\n{}pub enum {} {{\n{}}}\n",
+            DERIVE, attribute, name, body
+        ));
+        if payloads && !matches!(self.enum_encoding, EnumEncoding::Tagged) {
+            self.representation(name, &units, &tagged);
+        }
+    }
+
+    /// `{name}Repr` reads unit variants as strings through `{name}Unit`, and
+    /// variants with a payload as tagged through `{name}Tagged` or bare.
+    fn representation(&mut self, name: &str, units: &[&String], tagged: &[(&String, String)]) {
+        let mut arms = String::new();
+        let mut conversions = String::new();
+        if !units.is_empty() {
+            arms.push_str(&format!("    Unit({}Unit),\n", name));
+            let mut unit = String::new();
+            for variant in units {
+                unit.push_str(&format!("    {},\n", variant));
+                conversions.push_str(&format!(
+                    "            {0}Repr::Unit({0}Unit::{1}) => {0}::{1},\n",
+                    name, variant
+                ));
+            }
+            self.declarations.push(format!(
+                "#[derive(serde::Deserialize)]\nenum {}Unit {{\n{}}}\n",
+                name, unit
+            ));
+        }
+        if matches!(self.enum_encoding, EnumEncoding::String) {
+            arms.push_str(&format!("    Tagged({}Tagged),\n", name));
+            let mut variants = String::new();
+            for (variant, payload) in tagged {
+                variants.push_str(&format!("    {}({}),\n", variant, payload));
+                conversions.push_str(&format!(
+                    "            {0}Repr::Tagged({0}Tagged::{1}(value)) => {0}::{1}(value),\n",
+                    name, variant
+                ));
+            }
+            self.declarations.push(format!(
+                "#[derive(serde::Deserialize)]\n#[serde(tag = \"tag\", content = \"value\")]\nenum {}Tagged {{\n{}}}\n",
+                name, variants
+            ));
+        } else {
+            for (variant, payload) in tagged {
+                arms.push_str(&format!("    {}({}),\n", variant, payload));
+                conversions.push_str(&format!(
+                    "            {0}Repr::{1}(value) => {0}::{1}(value),\n",
+                    name, variant
+                ));
+            }
+        }
+        self.declarations.push(format!(
+            "#[derive(serde::Deserialize)]\n#[serde(untagged)]\nenum {0}Repr {{\n{1}}}\n\nimpl From<{0}Repr> for {0} {{\n    fn from(repr: {0}Repr) -> Self {{\n        match repr {{\n{2}        }}\n    }}\n}}\n",
+            name, arms, conversions
+        ));
+    }
+
+    /// Unions are declared as untagged enums rather than aliased.
+    fn alias(&mut self, name: &str, generics: &str, ty: &Type) {
+        match ty {
+            Type::Union(members) => self.union(name, generics, members),
+            Type::Struct(fields) => self.structure(name, generics, fields),
+            ty => match self.ty(ty, name, generics) {
+                Some(aliased) => self
+                    .declarations
+                    .push(format!("pub type {}{} = {};\n", name, generics, aliased)),
+                None => self.declarations.push(format!(
+                    "// '{}' is skipped, '{}' is not data.\n",
+                    name,
+                    ty.print()
+                )),
+            },
+        }
+    }
+
+    fn union(&mut self, name: &str, generics: &str, members: &[Type]) {
+        let mut body = String::new();
+        let mut variants: Vec<String> = vec![];
+        for (i, member) in members.iter().enumerate() {
+            let mut variant = variant_name(member);
+            if variants.contains(&variant) {
+                variant = format!("{}{}", variant, i + 1);
+            }
+            let nested = format!("{}{}", name, variant);
+            if let Some(ty) = self.ty(member, &nested, generics) {
+                body.push_str(&format!("    {}({}),\n", variant, ty));
+                variants.push(variant);
+            }
+        }
+        self.declarations.push(format!(
+            "{}\n#[serde(untagged)]\npub enum {}{} {{\n{}}}\n",
+            DERIVE, name, generics, body
+        ));
+    }
+
+    /// Rust type of a Mia type, declaring anonymous structs and unions under
+    /// `name`. `None` when the type is not data.
+    fn ty(&mut self, ty: &Type, name: &str, generics: &str) -> Option<String> {
+        Some(match ty {
+            Type::Builtin(BuiltinType::Num | BuiltinType::Int) => "f64".to_string(),
+            Type::Builtin(BuiltinType::String) => "String".to_string(),
+            Type::Builtin(BuiltinType::Bool) => "bool".to_string(),
+            Type::Builtin(BuiltinType::Unit) => "()".to_string(),
+            Type::Nested(base, args) => match (&**base, &args[..]) {
+                (Type::Builtin(BuiltinType::Array), [item]) => {
+                    format!("Vec<{}>", self.ty(item, name, generics)?)
+                }
+                (Type::Builtin(BuiltinType::Map), [_, value]) => format!(
+                    "std::collections::BTreeMap<String, {}>",
+                    self.ty(value, name, generics)?
+                ),
+                (Type::Explicit(base), args) => {
+                    let args = args
+                        .iter()
+                        .map(|arg| self.ty(arg, name, generics))
+                        .collect::<Option<Vec<String>>>()?;
+                    format!("{}<{}>", type_name(&base.0), args.join(", "))
+                }
+                _ => return None,
+            },
+            Type::Nullable(ty) => format!("Option<{}>", self.ty(ty, name, generics)?),
+            Type::Explicit(ty) | Type::Param(ty) => type_name(&ty.0),
+            Type::Struct(fields) => {
+                self.structure(name, generics, fields);
+                format!("{}{}", name, generics)
+            }
+            Type::Union(members) => {
+                self.union(name, generics, members);
+                format!("{}{}", name, generics)
+            }
+            // Arrays and maps need their item type, functions are not data.
+            Type::Builtin(BuiltinType::Array | BuiltinType::Map)
+            | Type::Generic(_, _)
+            | Type::Fn(_, _)
+            | Type::Enum(_)
+            | Type::Infer => return None,
+        })
+    }
+}
+
+/// `.d.ts` declarations for the structs, enums, aliases and exported values
+/// of a module.
+pub fn typescript(
+    program: &Program,
+    checker: &TypeChecker,
+    source: &str,
+    enum_encoding: EnumEncoding,
+) -> String {
+    let names = program
+        .0
+        .iter()
+        .filter_map(|statement| match statement {
+            Statement::Struct(_, name, params, fields, _) if params.is_empty() => {
+                Some((Type::Struct(fields.clone()), type_name(name.lexeme)))
+            }
+            Statement::Enum(_, name, variants) => {
+                Some((Type::Enum(variants.clone()), type_name(name.lexeme)))
+            }
+            _ => None,
+        })
+        .collect();
+    let generator = TypeScriptGenerator {
+        enum_encoding,
+        names,
+    };
+    let mut out = format!("// Generated by mia from {}, do not edit.\n", source);
+    for statement in &program.0 {
+        let declaration = match statement {
+            Statement::Struct(_, name, params, fields, _) => format!(
+                "export interface {}{} {}\n",
+                type_name(name.lexeme),
+                generics(params),
+                generator.structure(fields, 0)
+            ),
+            Statement::Enum(_, name, variants) => format!(
+                "export type {} = {};\n",
+                type_name(name.lexeme),
+                generator.enumeration(variants)
+            ),
+            Statement::Type(_, name, ty) => {
+                let (params, ty) = match ty {
+                    Type::Generic(params, ty) => (generics(params), &**ty),
+                    ty => (String::new(), ty),
+                };
+                match generator.ty(ty, 0) {
+                    Some(ty) => format!(
+                        "export type {}{} = {};\n",
+                        type_name(name.lexeme),
+                        params,
+                        ty
+                    ),
+                    None => format!(
+                        "// '{}' is skipped, '{}' is not data.\n",
+                        name.lexeme,
+                        ty.print()
+                    ),
+                }
+            }
+            Statement::Variable(true, name, declared, _) => {
+                let ty = declared.clone().or_else(|| checker.variable(name.lexeme));
+                match ty.as_ref().and_then(|ty| generator.ty(ty, 0)) {
+                    Some(ty) => format!("export declare const {}: {};\n", name.lexeme, ty),
+                    None => format!("// '{}' is skipped, it is not data.\n", name.lexeme),
+                }
+            }
+            _ => continue,
+        };
+        out.push('\n');
+        out.push_str(&declaration);
+    }
+    out
+}
+
+struct TypeScriptGenerator {
+    enum_encoding: EnumEncoding,
+    /// Checked types are structural, values of a declared struct or enum
+    /// are written with its name.
+    names: Vec<(Type, String)>,
+}
+
+impl TypeScriptGenerator {
+    /// Object type with one property per line, indented by `depth`.
+    fn structure(&self, fields: &[FieldDeclaration], depth: usize) -> String {
+        let indent = "    ".repeat(depth);
+        let mut body = "{\n".to_string();
+        for FieldDeclaration(field, ty, _) in fields {
+            match self.ty(ty, depth + 1) {
+                Some(ty) => {
+                    body.push_str(&format!("{}    {}: {};\n", indent, property(&field.0), ty))
+                }
+                None => body.push_str(&format!(
+                    "{}    // '{}' is skipped, '{}' is not data.\n",
+                    indent,
+                    field.0,
+                    ty.print()
+                )),
+            }
+        }
+        body.push_str(&indent);
+        body.push('}');
+        body
+    }
+
+    /// Written as the formatter writes variants with `enum_encoding`.
+    fn enumeration(&self, variants: &[VariantDeclaration]) -> String {
+        let mut options: Vec<String> = vec![];
+        for option in variants
+            .iter()
+            .filter_map(|VariantDeclaration(name, payload)| {
+                let payload = match payload {
+                    Some(payload) => Some(self.ty(payload, 0)?),
+                    None => None,
+                };
+                Some(match (self.enum_encoding, payload) {
+                    (EnumEncoding::Untagged, Some(payload)) => payload,
+                    (EnumEncoding::String | EnumEncoding::Untagged, None) => {
+                        format!("\"{}\"", name.0)
+                    }
+                    (EnumEncoding::Tagged, None) => format!("{{ tag: \"{}\" }}", name.0),
+                    (_, Some(payload)) => {
+                        format!("{{ tag: \"{}\"; value: {} }}", name.0, payload)
+                    }
+                })
+            })
+        {
+            // Untagged payloads can have the same type.
+            if !options.contains(&option) {
+                options.push(option);
+            }
+        }
+        if options.is_empty() {
+            "never".to_string()
+        } else {
+            options.join(" | ")
+        }
+    }
+
+    /// TypeScript type of a Mia type, `None` when it is not data.
+    fn ty(&self, ty: &Type, depth: usize) -> Option<String> {
+        if let Some((_, name)) = self.names.iter().find(|named| named.0 == *ty) {
+            return Some(name.clone());
+        }
+        Some(match ty {
+            Type::Builtin(BuiltinType::Num | BuiltinType::Int) => "number".to_string(),
+            Type::Builtin(BuiltinType::String) => "string".to_string(),
+            Type::Builtin(BuiltinType::Bool) => "boolean".to_string(),
+            Type::Builtin(BuiltinType::Unit) => "null".to_string(),
+            Type::Builtin(BuiltinType::Array) => "unknown[]".to_string(),
+            Type::Builtin(BuiltinType::Map) => "Record<string, unknown>".to_string(),
+            Type::Nested(base, args) => match (&**base, &args[..]) {
+                (Type::Builtin(BuiltinType::Array), [item]) => {
+                    let item = self.ty(item, depth)?;
+                    if item.contains(" | ") {
+                        format!("({})[]", item)
+                    } else {
+                        format!("{}[]", item)
+                    }
+                }
+                (Type::Builtin(BuiltinType::Map), [_, value]) => {
+                    format!("Record<string, {}>", self.ty(value, depth)?)
+                }
+                (Type::Explicit(base), args) => {
+                    let args = args
+                        .iter()
+                        .map(|arg| self.ty(arg, depth))
+                        .collect::<Option<Vec<String>>>()?;
+                    format!("{}<{}>", type_name(&base.0), args.join(", "))
+                }
+                _ => return None,
+            },
+            Type::Nullable(ty) => format!("{} | null", self.ty(ty, depth)?),
+            Type::Explicit(ty) | Type::Param(ty) => type_name(&ty.0),
+            Type::Struct(fields) => self.structure(fields, depth),
+            Type::Enum(variants) => self.enumeration(variants),
+            Type::Union(members) => members
+                .iter()
+                .map(|member| self.ty(member, depth))
+                .collect::<Option<Vec<String>>>()?
+                .join(" | "),
+            Type::Generic(_, _) | Type::Fn(_, _) | Type::Infer => return None,
+        })
+    }
+}
+
+fn generics(params: &[QualifiedName]) -> String {
+    if params.is_empty() {
+        String::new()
+    } else {
+        let params: Vec<&str> = params.iter().map(|p| &*p.0).collect();
+        format!("<{}>", params.join(", "))
+    }
+}
+
+/// `String | Array<Port>` gives `String` and `Array`.
+fn variant_name(ty: &Type) -> String {
+    match ty {
+        Type::Nested(base, _) => variant_name(base),
+        Type::Nullable(ty) => variant_name(ty),
+        Type::Builtin(BuiltinType::Num) => "Number".to_string(),
+        Type::Explicit(name) | Type::Param(name) => type_name(&name.0),
+        Type::Struct(_) => "Struct".to_string(),
+        ty => ty.print(),
+    }
+}
+
+/// Declarations of a schema module that the script does not import are
+/// qualified by the module: `Schema00038.Env1` gives `Schema00038Env1`.
+fn type_name(name: &str) -> String {
+    name.replace('.', "")
+}
+
+/// `containerPort` gives `container_port`, and `runs-on` gives `runs_on`.
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        if c.is_alphanumeric() {
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push('_');
+        }
+    }
+    if !snake.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+        snake.insert(0, '_');
+    }
+    snake
+}
+
+/// `runs-on` gives `RunsOn`.
+fn pascal_case(name: &str) -> String {
+    let mut pascal = String::new();
+    for part in name.split(|c: char| !c.is_alphanumeric()) {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            pascal.push(first.to_ascii_uppercase());
+            pascal.push_str(chars.as_str());
+        }
+    }
+    pascal
+}
+
+/// Property names that are not identifiers are quoted.
+fn property(name: &str) -> String {
+    let is_identifier = name.starts_with(|c: char| c.is_alphabetic() || c == '_' || c == '$')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '$');
+    if is_identifier {
+        name.to_string()
+    } else {
+        format!("{:?}", name)
+    }
+}
+
+/// Keywords are written as raw identifiers, where Rust allows it.
+fn identifier(name: &str) -> String {
+    const KEYWORDS: &[&str] = &[
+        "as", "async", "await", "box", "break", "const", "continue", "do", "dyn", "else", "enum",
+        "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop", "macro",
+        "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "static",
+        "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual",
+        "where", "while", "yield",
+    ];
+    match name {
+        "self" | "super" | "crate" => format!("{}_", name),
+        name if KEYWORDS.contains(&name) => format!("r#{}", name),
+        name => name.to_string(),
+    }
+}
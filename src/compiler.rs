@@ -1,40 +1,173 @@
+use crate::parser::{FieldDeclaration, QualifiedName, Type};
 use crate::token::Token;
+use crate::trace::{Phase, Tracer};
 
 #[derive(Clone, Debug)]
 struct Local {
     name: String,
 }
 
+/// Variable captured by a function, from a local of the enclosing function or
+/// from one of its own upvalues.
+#[derive(Clone, Debug)]
+pub struct Upvalue {
+    pub index: usize,
+    pub is_local: bool,
+}
+
 #[derive(Clone, Debug)]
 pub struct Compiler {
+    enclosing: Option<Box<Compiler>>,
     locals: Vec<Local>,
-    debug: bool,
+    pub upvalues: Vec<Upvalue>,
+    structs: Vec<(QualifiedName, Vec<FieldDeclaration>)>,
+    aliases: Vec<(QualifiedName, Type)>,
+    constraints: Vec<StructConstraint>,
+    tracer: Tracer,
+}
+
+/// Constraint of a struct field, checked by the predicate stored in the local
+/// named `predicate`. The description completes error messages.
+#[derive(Clone, Debug)]
+pub struct StructConstraint {
+    pub name: QualifiedName,
+    pub path: Vec<QualifiedName>,
+    pub predicate: String,
+    pub description: String,
 }
 
 impl Compiler {
-    pub fn init(debug: bool) -> Self {
+    pub fn init(tracer: Tracer) -> Self {
+        Compiler {
+            enclosing: None,
+            locals: vec![],
+            upvalues: vec![],
+            structs: vec![],
+            aliases: vec![],
+            constraints: vec![],
+            tracer,
+        }
+    }
+
+    /// Compiler for a function body: its locals start empty, as the callee
+    /// frame only holds the arguments and the function itself. Variables of
+    /// `self` are reached through upvalues, `end` gives it back.
+    pub fn enclosed(self) -> Self {
         Compiler {
             locals: vec![],
-            debug,
+            upvalues: vec![],
+            structs: self.structs.clone(),
+            aliases: self.aliases.clone(),
+            constraints: self.constraints.clone(),
+            tracer: self.tracer.clone(),
+            enclosing: Some(Box::new(self)),
         }
     }
 
+    pub fn end(&mut self) -> Compiler {
+        *self.enclosing.take().expect("Not a function compiler")
+    }
+
     pub fn add_variable(&mut self, token: &Token) -> usize {
+        self.add_local(token.lexeme)
+    }
+
+    /// Adds a local slot. Temporaries use an empty name, which no identifier
+    /// can resolve to.
+    pub fn add_local(&mut self, name: &str) -> usize {
         self.locals.push(Local {
-            name: token.lexeme.to_string(),
+            name: name.to_string(),
         });
 
-        if self.debug {
-            println!(
-                "[Compiler] added local '{}' in position {}",
-                token.lexeme.to_string(),
-                self.locals.len() - 1
-            );
-        }
+        self.tracer.event(Phase::Compiler, "local", || {
+            vec![
+                ("name", name.to_string()),
+                ("slot", (self.locals.len() - 1).to_string()),
+            ]
+        });
 
         self.locals.len() - 1
     }
 
+    /// Returns the mark to give back to `end_scope`, which forgets every
+    /// local added since.
+    pub fn begin_scope(&self) -> usize {
+        self.locals.len()
+    }
+
+    pub fn end_scope(&mut self, mark: usize) {
+        self.locals.truncate(mark);
+    }
+
+    pub fn add_struct(&mut self, token: &Token, fields: &[FieldDeclaration]) {
+        self.structs
+            .push((QualifiedName(token.lexeme.to_string()), fields.to_vec()));
+    }
+
+    pub fn add_alias(&mut self, token: &Token, ty: &Type) {
+        self.aliases
+            .push((QualifiedName(token.lexeme.to_string()), ty.clone()));
+    }
+
+    pub fn add_constraint(&mut self, constraint: StructConstraint) {
+        self.constraints.push(constraint);
+    }
+
+    pub fn resolve_constraints(&self, name: &QualifiedName) -> Vec<StructConstraint> {
+        self.constraints
+            .iter()
+            .filter(|c| &c.name == name)
+            .cloned()
+            .collect()
+    }
+
+    pub fn resolve_alias(&self, name: &QualifiedName) -> Option<Type> {
+        self.aliases
+            .iter()
+            .rev()
+            .find(|a| &a.0 == name)
+            .map(|a| a.1.clone())
+    }
+
+    pub fn resolve_struct(&self, name: &QualifiedName) -> Option<Vec<FieldDeclaration>> {
+        if let Some(s) = self.structs.iter().rev().find(|s| &s.0 == name) {
+            return Some(s.1.clone());
+        }
+        match self.resolve_alias(name) {
+            Some(Type::Explicit(aliased)) if &aliased != name => self.resolve_struct(&aliased),
+            Some(Type::Nested(base, _)) => match *base {
+                Type::Explicit(aliased) => self.resolve_struct(&aliased),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub fn resolve_upvalue(&mut self, token: &Token) -> Option<usize> {
+        let enclosing = self.enclosing.as_mut()?;
+        let upvalue = if let Some(i) = enclosing.resolve_variable(token) {
+            Upvalue {
+                index: i,
+                is_local: true,
+            }
+        } else {
+            Upvalue {
+                index: enclosing.resolve_upvalue(token)?,
+                is_local: false,
+            }
+        };
+
+        if let Some(i) = self
+            .upvalues
+            .iter()
+            .position(|u| u.index == upvalue.index && u.is_local == upvalue.is_local)
+        {
+            return Some(i);
+        }
+        self.upvalues.push(upvalue);
+        Some(self.upvalues.len() - 1)
+    }
+
     pub fn resolve_variable(&self, token: &Token) -> Option<usize> {
         for (offset, local) in self.locals.iter().enumerate().rev() {
             if local.name == token.lexeme {
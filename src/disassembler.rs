@@ -0,0 +1,113 @@
+//! Listing of the bytecode of a chunk, for debugging the compiler.
+use crate::bytecode::{Chunk, Opcode};
+use crate::formatter::{EnumEncoding, JsonFmt};
+use crate::parser::Object;
+use std::fmt::Write;
+
+/// Disassembles the chunk named `name` followed by the chunks of the functions
+/// it creates. Each instruction is listed with its offset, its span and its
+/// operands, constants being written after them. The source line of the
+/// following instructions is written whenever it changes.
+pub fn disassemble(chunk: &Chunk, name: &str, source: &str) -> String {
+    let mut out = String::new();
+    Disassembler {
+        lines: source.lines().collect(),
+        formatter: JsonFmt::new(false, EnumEncoding::Tagged),
+    }
+    .chunk(&mut out, chunk, name);
+    out
+}
+
+struct Disassembler<'s> {
+    lines: Vec<&'s str>,
+    /// Constants are written as JSON, variants tagged to tell them from strings.
+    formatter: JsonFmt,
+}
+
+impl Disassembler<'_> {
+    fn chunk(&self, out: &mut String, chunk: &Chunk, name: &str) {
+        writeln!(out, "== {} ==", name).unwrap();
+        let mut line = None;
+        for (offset, op) in chunk.code.iter().enumerate() {
+            let span = chunk.spans.get(offset).unwrap_or_default();
+            if line != Some(span.line) {
+                line = Some(span.line);
+                if let Some(text) = span.line.checked_sub(1).and_then(|l| self.lines.get(l)) {
+                    writeln!(out, "; {:>4} | {}", span.line, text.trim_end()).unwrap();
+                }
+            }
+            let location = format!("{}:{}", span.line, span.col);
+            let (mnemonic, operands, constants) = self.instruction(op);
+            let mut listing = format!("{:04} {:>8} {:<18}{}", offset, location, mnemonic, operands);
+            let constants: Vec<String> = constants
+                .iter()
+                .map(|i| match chunk.constants.get(*i as usize) {
+                    Some(constant) => self.formatter.format(constant),
+                    None => format!("<missing constant {}>", i),
+                })
+                .collect();
+            if !constants.is_empty() {
+                listing = format!("{:<40} ; {}", listing, constants.join(" "));
+            }
+            writeln!(out, "{}", listing.trim_end()).unwrap();
+        }
+
+        for constant in &chunk.constants {
+            if let Object::Function(prototype, _) = constant {
+                writeln!(out).unwrap();
+                let qualified = format!("{} > {}/{}", name, prototype.name, prototype.arity);
+                self.chunk(out, &prototype.chunk, &qualified);
+            }
+        }
+    }
+
+    /// Mnemonic, written operands and the constants they refer to.
+    fn instruction(&self, op: &Opcode) -> (&'static str, String, Vec<u32>) {
+        match op {
+            Opcode::Pop => ("Pop", String::new(), vec![]),
+            Opcode::Nil => ("Nil", String::new(), vec![]),
+            Opcode::Load(slot) => ("Load", slot.to_string(), vec![]),
+            Opcode::Store(slot) => ("Store", slot.to_string(), vec![]),
+            Opcode::Constant(i) => ("Constant", i.to_string(), vec![*i]),
+            Opcode::Struct(len) => ("Struct", len.to_string(), vec![]),
+            Opcode::Array(len) => ("Array", len.to_string(), vec![]),
+            Opcode::Map(len) => ("Map", len.to_string(), vec![]),
+            Opcode::Index => ("Index", String::new(), vec![]),
+            Opcode::Next(slot, target) => ("Next", format!("{} -> {:04}", slot, target), vec![]),
+            Opcode::Append(slot) => ("Append", slot.to_string(), vec![]),
+            Opcode::LoadUpvalue(slot) => ("LoadUpvalue", slot.to_string(), vec![]),
+            Opcode::Closure(i) => ("Closure", i.to_string(), vec![*i]),
+            Opcode::Call => ("Call", String::new(), vec![]),
+            Opcode::Variant(i) => ("Variant", i.to_string(), vec![*i]),
+            Opcode::Unwrap => ("Unwrap", String::new(), vec![]),
+            Opcode::Jump(target) => ("Jump", format!("-> {:04}", target), vec![]),
+            Opcode::JumpIfFalse(target) => ("JumpIfFalse", format!("-> {:04}", target), vec![]),
+            Opcode::JumpIfTrue(target) => ("JumpIfTrue", format!("-> {:04}", target), vec![]),
+            Opcode::JumpIfNotVariant(i, target) => (
+                "JumpIfNotVariant",
+                format!("{} -> {:04}", i, target),
+                vec![*i],
+            ),
+            Opcode::JumpIfNotKind(i, target) => {
+                ("JumpIfNotKind", format!("{} -> {:04}", i, target), vec![*i])
+            }
+            Opcode::Merge => ("Merge", String::new(), vec![]),
+            Opcode::DeepMerge => ("DeepMerge", String::new(), vec![]),
+            Opcode::Update(i) => ("Update", i.to_string(), vec![*i]),
+            Opcode::Add => ("Add", String::new(), vec![]),
+            Opcode::Multiply => ("Multiply", String::new(), vec![]),
+            Opcode::Negate => ("Negate", String::new(), vec![]),
+            Opcode::Not => ("Not", String::new(), vec![]),
+            Opcode::Equal => ("Equal", String::new(), vec![]),
+            Opcode::Less => ("Less", String::new(), vec![]),
+            Opcode::Greater => ("Greater", String::new(), vec![]),
+            Opcode::Check(path, message) => (
+                "Check",
+                format!("{} {}", path, message),
+                vec![*path, *message],
+            ),
+            Opcode::Assert(i) => ("Assert", i.to_string(), vec![*i]),
+            Opcode::Return => ("Return", String::new(), vec![]),
+        }
+    }
+}
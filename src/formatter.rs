@@ -1,34 +1,47 @@
 use crate::parser::Object;
 
-pub struct JsonFmt {}
+/// How enum variants are written.
+#[derive(Clone, Copy, Debug)]
+pub enum EnumEncoding {
+    /// `"Tcp"`, variants with a payload fall back to `Tagged`.
+    String,
+    /// `{"tag":"Git","value":...}`
+    Tagged,
+    /// The payload alone, or the variant name when there is none.
+    Untagged,
+}
+
+impl EnumEncoding {
+    pub fn parse(name: &str) -> Option<EnumEncoding> {
+        match name {
+            "string" => Some(EnumEncoding::String),
+            "tagged" => Some(EnumEncoding::Tagged),
+            "untagged" => Some(EnumEncoding::Untagged),
+            _ => None,
+        }
+    }
+}
+
+pub struct JsonFmt {
+    /// Leave out struct fields and map entries set to nil instead of writing `null`.
+    skip_nil: bool,
+    enum_encoding: EnumEncoding,
+}
 
 impl JsonFmt {
-    pub fn new() -> Self {
-        JsonFmt {}
+    pub fn new(skip_nil: bool, enum_encoding: EnumEncoding) -> Self {
+        JsonFmt {
+            skip_nil,
+            enum_encoding,
+        }
     }
 
     pub fn format(&self, object: &Object) -> String {
         match object {
             Object::Num(f) => f.to_string(),
-            Object::String(s) => {
-                format!("\"{}\"", s)
-            }
-            Object::Struct(fields) => {
-                let mut s = "{".to_string();
-                let mut iter = fields.iter().peekable();
-                while let Some(field) = iter.next() {
-                    s.push('"');
-                    s.push_str(&field.0 .0); // TODO this is not legit
-                    s.push('"');
-                    s.push(':');
-                    s.push_str(&self.format(&field.1));
-                    if iter.peek().is_some() {
-                        s.push(',');
-                    }
-                }
-                s.push('}');
-                s
-            }
+            Object::String(s) => quote(s),
+            Object::Struct(fields) => self.object(fields.iter().map(|f| (&*f.0, &f.1))),
+            Object::Map(entries) => self.object(entries.iter().map(|e| (&*e.0, &e.1))),
             Object::Array(a) => {
                 let mut s = "[".to_string();
                 let mut iter = a.iter().peekable();
@@ -41,8 +54,57 @@ impl JsonFmt {
                 s.push(']');
                 s
             }
+            Object::Variant(tag, payload) => match (self.enum_encoding, payload) {
+                (EnumEncoding::Untagged, Some(payload)) => self.format(payload),
+                (EnumEncoding::String | EnumEncoding::Untagged, None) => quote(tag),
+                (EnumEncoding::Tagged, None) => format!("{{\"tag\":{}}}", quote(tag)),
+                (_, Some(payload)) => {
+                    format!(
+                        "{{\"tag\":{},\"value\":{}}}",
+                        quote(tag),
+                        self.format(payload)
+                    )
+                }
+            },
+            Object::Bool(b) => b.to_string(),
             Object::Nil => "null".to_string(),
-            Object::Function(_, name, _, _) => format!("<fn {}>", name),
+            Object::Function(prototype, _) => format!("<fn {}>", prototype.name),
+            Object::NativeFunction(native) => format!("<fn {}>", native.name),
+        }
+    }
+
+    fn object<'o>(&self, entries: impl Iterator<Item = (&'o str, &'o Object)>) -> String {
+        let mut s = "{".to_string();
+        let mut iter = entries
+            .filter(|e| !self.skip_nil || !matches!(e.1, Object::Nil))
+            .peekable();
+        while let Some((key, value)) = iter.next() {
+            s.push_str(&quote(key));
+            s.push(':');
+            s.push_str(&self.format(value));
+            if iter.peek().is_some() {
+                s.push(',');
+            }
+        }
+        s.push('}');
+        s
+    }
+}
+
+/// JSON string literal, escaping quotes, backslashes and control characters.
+pub fn quote(s: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
         }
     }
+    quoted.push('"');
+    quoted
 }
@@ -0,0 +1,265 @@
+//! Mia, a typed configuration language compiling to JSON.
+//!
+//! The `mia` binary wraps these functions. Build scripts can use `rust` or
+//! `typescript` to keep the types of a program in step with the Mia
+//! declarations it reads:
+//!
+//! ```no_run
+//! // build.rs
+//! let out = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("config.rs");
+//! std::fs::write(out, mia::rust("config.m", mia::EnumEncoding::String)).unwrap();
+//! println!("cargo:rerun-if-changed=config.m");
+//! ```
+//!
+//! Errors found before evaluation are reported on stderr and exit the
+//! process, evaluation errors are returned.
+use crate::bytecode::Chunk;
+use crate::formatter::JsonFmt;
+use crate::json::Json;
+use crate::parser::{Constraint, Parser, Program, QualifiedName, Statement, Type};
+use crate::scanner::Scanner;
+use crate::token::TokenType;
+use crate::type_checker::TypeChecker;
+use crate::vm::VM;
+use std::path::Path;
+
+mod bytecode;
+mod codegen;
+mod compiler;
+mod disassembler;
+mod encoding;
+mod formatter;
+mod json;
+mod parser;
+mod prelude;
+mod regex;
+mod scanner;
+mod schema;
+mod token;
+mod trace;
+mod type_checker;
+mod vm;
+
+pub use crate::bytecode::Span;
+pub use crate::formatter::EnumEncoding;
+pub use crate::trace::{Phase, TraceFormat, Tracer};
+pub use crate::vm::RuntimeError;
+
+/// JSON value of the script's `main`, or the error that stopped its
+/// evaluation.
+pub fn run_file(
+    f: impl AsRef<Path>,
+    tracer: &Tracer,
+    skip_nil: bool,
+    enum_encoding: EnumEncoding,
+) -> Result<String, RuntimeError> {
+    checked(f.as_ref(), tracer, |ast, _| {
+        let mut main = Chunk::init(tracer.clone());
+        main.compile(&ast.0);
+
+        let mut vm = VM::init(main, tracer.clone());
+        let result = vm.run()?;
+
+        let formatter = JsonFmt::new(skip_nil, enum_encoding);
+        Ok(formatter.format(&result))
+    })
+}
+
+/// Bytecode listing of a type checked script, followed by the functions it
+/// creates.
+pub fn disassemble(f: impl AsRef<Path>) -> String {
+    let f = f.as_ref();
+    checked(f, &Tracer::default(), |ast, _| {
+        let mut main = Chunk::init(Tracer::default());
+        main.compile(&ast.0);
+        let source = std::fs::read_to_string(f).unwrap();
+        disassembler::disassemble(&main, "main", &source)
+    })
+}
+
+/// JSON Schema of the declarations of a type checked script.
+pub fn export_schema(f: impl AsRef<Path>, tracer: &Tracer, enum_encoding: EnumEncoding) -> String {
+    checked(f.as_ref(), tracer, |ast, _| {
+        let formatter = JsonFmt::new(false, enum_encoding);
+        formatter.format(&schema::export(ast, enum_encoding))
+    })
+}
+
+/// Rust declarations, deriving `serde::Deserialize`, of the structs, enums and
+/// aliases of a type checked script.
+pub fn rust(f: impl AsRef<Path>, enum_encoding: EnumEncoding) -> String {
+    let f = f.as_ref();
+    checked(f, &Tracer::default(), |ast, _| {
+        codegen::rust(ast, &f.display().to_string(), enum_encoding)
+    })
+}
+
+/// TypeScript declarations of the structs, enums, aliases and exported values
+/// of a type checked script, for a `.d.ts` file.
+pub fn typescript(f: impl AsRef<Path>, enum_encoding: EnumEncoding) -> String {
+    let f = f.as_ref();
+    checked(f, &Tracer::default(), |ast, checker| {
+        codegen::typescript(ast, checker, &f.display().to_string(), enum_encoding)
+    })
+}
+
+/// Mia declarations of a JSON Schema file, named after the file unless the
+/// schema has a title.
+pub fn import_schema(path: impl AsRef<Path>) -> String {
+    schema_module(path.as_ref()).source
+}
+
+fn schema_module(path: &Path) -> schema::Module {
+    let source = std::fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path.display(), e);
+        std::process::exit(66)
+    });
+    let schema: Json = json::parse(&source).unwrap_or_else(|e| {
+        eprintln!("{}:{}", path.display(), e);
+        std::process::exit(65)
+    });
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    schema::import(&schema, &name)
+}
+
+/// Parses the script with the schemas it imports, and type checks it.
+fn checked<T>(f: &Path, tracer: &Tracer, then: impl FnOnce(&Program, &TypeChecker) -> T) -> T {
+    let source = std::fs::read_to_string(f).unwrap();
+    let ast = parse(&source, tracer);
+    let schemas = imported_schemas(f, &ast);
+    let ast = with_schemas(ast, &schemas, tracer);
+
+    let mut checker = TypeChecker::init(tracer.clone());
+    checker.check(&ast);
+    then(&ast, &checker)
+}
+
+/// Declarations generated from a schema module imported by the script.
+struct Schema {
+    source: String,
+    /// Struct and field names, with the location in the module of the
+    /// property the field is declared from.
+    origins: Vec<(String, String, String)>,
+    /// Declarations the script does not import, with their name qualified by
+    /// the module, which no identifier can name.
+    hidden: Vec<(String, String)>,
+}
+
+/// Schemas imported by the script, relative to its directory.
+fn imported_schemas(f: &Path, ast: &Program) -> Vec<Schema> {
+    let dir = f.parent().unwrap_or_else(|| Path::new(""));
+    let mut modules: Vec<(&str, Vec<&str>)> = vec![];
+    for statement in &ast.0 {
+        if let Statement::Import(name, module) = statement {
+            let module = &module.lexeme[1..module.lexeme.len() - 1];
+            if !module.ends_with(".json") {
+                continue;
+            }
+            match modules.iter_mut().find(|m| m.0 == module) {
+                Some((_, names)) => names.push(name.lexeme),
+                None => modules.push((module, vec![name.lexeme])),
+            }
+        }
+    }
+    modules
+        .iter()
+        .map(|(module, imported)| {
+            let path = dir.join(module);
+            let schema::Module { source, origins } = schema_module(&path);
+            let origins = origins
+                .into_iter()
+                .map(|(name, field, pointer)| (name, field, format!("{}{}", module, pointer)))
+                .collect();
+            let prefix = schema::pascal_case(
+                &path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy())
+                    .unwrap_or_default(),
+            );
+            let hidden = parse(&source, &Tracer::default())
+                .0
+                .iter()
+                .filter_map(Statement::declared)
+                .filter(|(public, name)| !public || !imported.contains(&name.lexeme))
+                .map(|(_, name)| {
+                    (
+                        name.lexeme.to_string(),
+                        format!("{}.{}", prefix, name.lexeme),
+                    )
+                })
+                .collect();
+            Schema {
+                source,
+                origins,
+                hidden,
+            }
+        })
+        .collect()
+}
+
+/// Schema declarations come first, so the script can use the ones it
+/// imports.
+fn with_schemas<'a>(ast: Program<'a>, schemas: &'a [Schema], tracer: &Tracer) -> Program<'a> {
+    let mut statements = vec![];
+    for schema in schemas {
+        for mut statement in parse(&schema.source, tracer).0 {
+            locate(&mut statement, &schema.origins);
+            hide(&mut statement, &schema.hidden);
+            statements.push(statement);
+        }
+    }
+    statements.extend(ast.0);
+    Program(statements)
+}
+
+/// Constraints of imported fields name the property they come from, the
+/// lines of the generated declarations mean nothing to the script.
+fn locate(statement: &mut Statement, origins: &[(String, String, String)]) {
+    if let Statement::Struct(_, name, _, _, constraints) = statement {
+        *constraints = std::mem::take(constraints)
+            .into_iter()
+            .map(|constraint| {
+                match origins.iter().find(|o| {
+                    o.0 == name.lexeme && [QualifiedName(o.1.clone())] == constraint.path()
+                }) {
+                    Some((_, _, origin)) => {
+                        Constraint::Imported(origin.clone(), Box::new(constraint))
+                    }
+                    None => constraint,
+                }
+            })
+            .collect();
+    }
+}
+
+/// Renames the hidden declarations of a schema module, and the references to
+/// them. Schema modules only declare structs, enums and aliases.
+fn hide<'a>(statement: &mut Statement<'a>, hidden: &'a [(String, String)]) {
+    let (name, types): (_, Vec<&mut Type>) = match statement {
+        Statement::Struct(_, name, _, fields, _) => {
+            (name, fields.iter_mut().map(|f| &mut f.1).collect())
+        }
+        Statement::Enum(_, name, variants) => (
+            name,
+            variants.iter_mut().filter_map(|v| v.1.as_mut()).collect(),
+        ),
+        Statement::Type(_, name, ty) => (name, vec![ty]),
+        _ => return,
+    };
+    if let Some((_, qualified)) = hidden.iter().find(|h| h.0 == name.lexeme) {
+        name.lexeme = qualified;
+    }
+    for ty in types {
+        ty.rename(hidden);
+    }
+}
+
+fn parse<'a>(source: &'a str, tracer: &Tracer) -> Program<'a> {
+    let mut scanner = Scanner::init(source, tracer.clone());
+    let current = scanner.scan_token();
+    let mut parser = Parser::init(scanner, tracer.clone(), current);
+    parser.parse()
+}
@@ -1,57 +1,95 @@
-use crate::bytecode::Chunk;
-use crate::formatter::JsonFmt;
-use crate::parser::Parser;
-use crate::scanner::Scanner;
-use crate::token::TokenType;
-use crate::type_checker::TypeChecker;
-use crate::vm::VM;
+use mia::{EnumEncoding, Phase, TraceFormat, Tracer};
 use std::env::args;
 
-mod bytecode;
-mod compiler;
-mod formatter;
-mod parser;
-mod scanner;
-mod token;
-mod type_checker;
-mod vm;
-
 fn main() {
     let mut args = args();
     args.next();
-    let mut debug = false;
+    let mut args = args.peekable();
+    if args.peek().map(|a| a == "import-schema").unwrap_or(false) {
+        args.next();
+        let schema = args.next().expect("Usage: mia import-schema [schema.json]");
+        print!("{}", mia::import_schema(schema));
+        return;
+    }
+    let command = match args.peek().map(|a| a.as_str()) {
+        Some("schema") => Command::Schema,
+        Some("rust") => Command::Rust,
+        Some("typescript") => Command::TypeScript,
+        Some("disasm") => Command::Disasm,
+        _ => Command::Run,
+    };
+    if !matches!(command, Command::Run) {
+        args.next();
+    }
+
+    let mut phases = vec![];
+    let mut trace_file = None;
+    let mut trace_format = TraceFormat::Text;
+    let mut skip_nil = false;
+    let mut enum_encoding = EnumEncoding::String;
     let mut script = None;
-    while let Some(arg) = args.next() {
+    for arg in args {
         if arg == "-d" {
-            debug = true;
+            phases = Phase::ALL.to_vec();
+        } else if let Some(names) = arg.strip_prefix("--trace=") {
+            phases = names
+                .split(',')
+                .flat_map(|name| match name {
+                    "all" => Phase::ALL.to_vec(),
+                    name => vec![Phase::parse(name)
+                        .expect("--trace must list scanner, parser, checker, compiler, vm or all")],
+                })
+                .collect();
+        } else if let Some(path) = arg.strip_prefix("--trace-file=") {
+            trace_file = Some(path.to_string());
+        } else if let Some(format) = arg.strip_prefix("--trace-format=") {
+            trace_format = match format {
+                "text" => TraceFormat::Text,
+                "json" => TraceFormat::Json,
+                _ => panic!("--trace-format must be text or json"),
+            };
+        } else if arg == "--skip-nil" {
+            skip_nil = true;
+        } else if let Some(encoding) = arg.strip_prefix("--enum-encoding=") {
+            enum_encoding = EnumEncoding::parse(encoding)
+                .expect("--enum-encoding must be one of string, tagged or untagged");
         } else {
             script = Some(arg);
         }
     }
-    run_file(script.expect("Usage: rlox [script]"), debug);
+    let script = script.expect(
+        "Usage: mia [import-schema | schema | rust | typescript | disasm] [-d] [--trace=scanner,vm] [--trace-file=path] [--trace-format=json] [--skip-nil] [--enum-encoding=string] [script]",
+    );
+    let tracer = match trace_file {
+        Some(path) => Tracer::file(&path, &phases, trace_format).unwrap_or_else(|e| {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(73)
+        }),
+        None => Tracer::stderr(&phases, trace_format),
+    };
+    match command {
+        Command::Run => match mia::run_file(script, &tracer, skip_nil, enum_encoding) {
+            Ok(json) => println!("{}", json),
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(65)
+            }
+        },
+        Command::Schema => println!("{}", mia::export_schema(script, &tracer, enum_encoding)),
+        Command::Rust => print!("{}", mia::rust(script, enum_encoding)),
+        Command::TypeScript => print!("{}", mia::typescript(script, enum_encoding)),
+        Command::Disasm => print!("{}", mia::disassemble(script)),
+    }
 }
 
-fn run_file(f: String, debug: bool) {
-    let source = std::fs::read_to_string(f).unwrap();
-
-    let mut scanner = Scanner::init(&source, debug);
-
-    let current = scanner.scan_token();
-    let mut parser = Parser::init(scanner, debug, current);
-
-    let ast = parser.parse();
-
-    let mut checker = TypeChecker::init(debug);
-    checker.check(&ast);
-
-    let mut main = Chunk::init(debug);
-    main.compile(&ast.0);
-
-    let mut vm = VM::init(main, debug);
-    let result = vm.run();
-
-    let formatter = JsonFmt::new();
-    let str = formatter.format(&result);
-
-    println!("{}", str);
+enum Command {
+    Run,
+    /// JSON Schema of the declarations.
+    Schema,
+    /// Rust declarations for reading the output.
+    Rust,
+    /// TypeScript declarations for reading the output.
+    TypeScript,
+    /// Bytecode listing.
+    Disasm,
 }
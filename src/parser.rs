@@ -1,87 +1,292 @@
-use crate::bytecode::Chunk;
+use crate::bytecode::Prototype;
+use crate::prelude::Native;
 use crate::token::Token;
+use crate::trace::{Phase, Tracer};
 use crate::{Scanner, TokenType};
-use std::ops::{Add, Mul};
+use std::rc::Rc;
 use std::str::FromStr;
 
+/// Runtime value. Strings and collections are reference counted, so copying a
+/// value between locals and the stack is cheap; they are copied on write when
+/// shared.
 #[derive(Clone, Debug)]
 pub enum Object {
     Num(f64),
-    String(String),
-    Struct(Vec<(QualifiedName, Object)>),
-    Array(Vec<Object>),
-    Function(u8, String, Chunk, Type),
+    /// Shared with the constant it was loaded from.
+    String(Rc<str>),
+    Bool(bool),
+    /// Field names are shared with the constants naming them.
+    Struct(Rc<Vec<(Rc<str>, Object)>>),
+    Array(Rc<Vec<Object>>),
+    /// String keyed map, in insertion order.
+    Map(Rc<Vec<(Rc<str>, Object)>>),
+    /// Enum variant with its optional payload.
+    Variant(Rc<str>, Option<Rc<Object>>),
+    /// Compiled function with the values captured from enclosing scopes.
+    Function(Rc<Prototype>, Rc<Vec<Object>>),
+    NativeFunction(&'static Native),
     Nil,
 }
 
-impl Add<Object> for Object {
-    type Output = Object;
+/// Structural equality, functions are never equal.
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Object::Num(a), Object::Num(b)) => a == b,
+            (Object::String(a), Object::String(b)) => a == b,
+            (Object::Bool(a), Object::Bool(b)) => a == b,
+            (Object::Struct(a), Object::Struct(b)) => a == b,
+            (Object::Array(a), Object::Array(b)) => a == b,
+            (Object::Map(a), Object::Map(b)) => a == b,
+            (Object::Variant(a, payload), Object::Variant(b, other_payload)) => {
+                a == b && payload == other_payload
+            }
+            (Object::Nil, Object::Nil) => true,
+            _ => false,
+        }
+    }
+}
 
-    fn add(self, rhs: Object) -> Self::Output {
-        if let Object::String(lhs) = self {
-            if let Object::String(rhs) = rhs {
-                Object::String(lhs + &rhs)
-            } else {
-                panic!()
+impl Object {
+    /// Sum of two numbers or concatenation of two strings, None for other
+    /// operands.
+    pub fn checked_add(self, rhs: Object) -> Option<Object> {
+        match (self, rhs) {
+            (Object::String(lhs), Object::String(rhs)) => {
+                Some(Object::String(format!("{}{}", lhs, rhs).into()))
             }
-        } else if let Object::Num(lhs) = self {
-            if let Object::Num(rhs) = rhs {
-                Object::Num(lhs + rhs)
-            } else {
-                panic!()
+            (Object::Num(lhs), Object::Num(rhs)) => Some(Object::Num(lhs + rhs)),
+            _ => None,
+        }
+    }
+
+    pub fn checked_mul(self, rhs: Object) -> Option<Object> {
+        match (self, rhs) {
+            (Object::Num(lhs), Object::Num(rhs)) => Some(Object::Num(lhs * rhs)),
+            _ => None,
+        }
+    }
+
+    /// Recursive version of `merge`: structs and maps present on both sides
+    /// are merged instead of replaced.
+    pub fn deep_merge(self, rhs: Object) -> Option<Object> {
+        match (self, rhs) {
+            (Object::Struct(fields), Object::Struct(overrides)) => {
+                Some(Object::Struct(merge_entries(fields, overrides, true)))
             }
-        } else {
-            panic!()
+            (Object::Map(entries), Object::Map(overrides)) => {
+                Some(Object::Map(merge_entries(entries, overrides, true)))
+            }
+            _ => None,
         }
     }
-}
 
-impl Mul<Object> for Object {
-    type Output = Object;
+    /// Right-biased shallow merge of two structs or two maps: entries of `rhs`
+    /// replace the ones of `self`, new entries are appended. None for other
+    /// operands.
+    pub fn merge(self, rhs: Object) -> Option<Object> {
+        match (self, rhs) {
+            (Object::Struct(fields), Object::Struct(overrides)) => {
+                Some(Object::Struct(merge_entries(fields, overrides, false)))
+            }
+            (Object::Map(entries), Object::Map(overrides)) => {
+                Some(Object::Map(merge_entries(entries, overrides, false)))
+            }
+            _ => None,
+        }
+    }
 
-    fn mul(self, rhs: Object) -> Self::Output {
-        if let Object::Num(lhs) = self {
-            if let Object::Num(rhs) = rhs {
-                Object::Num(lhs * rhs)
-            } else {
-                panic!()
+    /// Merge of a `..base` literal: the overrides replace the fields of
+    /// `self`, except the ones named by `shape`, nested structs built from
+    /// dotted paths, which update the field of `self` recursively.
+    pub fn update(self, overrides: Object, shape: &Object) -> Option<Object> {
+        match (self, overrides) {
+            (Object::Struct(mut fields), Object::Struct(overrides)) => {
+                let updated = Rc::make_mut(&mut fields);
+                for (key, value) in Rc::unwrap_or_clone(overrides) {
+                    if let Some(entry) = updated.iter_mut().find(|e| e.0 == key) {
+                        let previous = std::mem::replace(&mut entry.1, Object::Nil);
+                        entry.1 = match shape.field(&key) {
+                            Some(nested) if matches!(previous, Object::Struct(_)) => previous
+                                .update(value, nested)
+                                .expect("both sides are structs"),
+                            _ => value,
+                        };
+                    } else {
+                        updated.push((key, value));
+                    }
+                }
+                Some(Object::Struct(fields))
             }
+            _ => None,
+        }
+    }
+
+    pub fn kind(&self) -> Kind {
+        match self {
+            Object::Num(_) => Kind::Number,
+            Object::String(_) => Kind::String,
+            Object::Bool(_) => Kind::Bool,
+            Object::Struct(_) => Kind::Struct,
+            Object::Array(_) => Kind::Array,
+            Object::Map(_) => Kind::Map,
+            Object::Variant(_, _) => Kind::Variant,
+            Object::Function(_, _) | Object::NativeFunction(_) => Kind::Fn,
+            Object::Nil => Kind::Nil,
+        }
+    }
+
+    /// Value of a struct field, None for missing fields and other objects.
+    pub fn field(&self, name: &str) -> Option<&Object> {
+        if let Object::Struct(fields) = self {
+            fields.iter().find(|f| &*f.0 == name).map(|f| &f.1)
         } else {
-            panic!()
+            None
         }
     }
 }
 
-impl Object {
-    pub fn as_str(&self) -> String {
-        if let Object::String(s) = self {
-            s.clone()
+/// Runtime representation of a value, which is what a match on a union can
+/// tell apart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Number,
+    String,
+    Bool,
+    Struct,
+    Array,
+    Map,
+    Variant,
+    Fn,
+    Nil,
+}
+
+impl Kind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Number => "Number",
+            Kind::String => "String",
+            Kind::Bool => "Bool",
+            Kind::Struct => "Struct",
+            Kind::Array => "Array",
+            Kind::Map => "Map",
+            Kind::Variant => "Variant",
+            Kind::Fn => "Fn",
+            Kind::Nil => "Nil",
+        }
+    }
+}
+
+/// The entries are only copied when shared.
+fn merge_entries(
+    mut entries: Rc<Vec<(Rc<str>, Object)>>,
+    overrides: Rc<Vec<(Rc<str>, Object)>>,
+    deep: bool,
+) -> Rc<Vec<(Rc<str>, Object)>> {
+    let merged = Rc::make_mut(&mut entries);
+    for (key, value) in Rc::unwrap_or_clone(overrides) {
+        if let Some(entry) = merged.iter_mut().find(|e| e.0 == key) {
+            let previous = std::mem::replace(&mut entry.1, Object::Nil);
+            entry.1 = match (&previous, &value) {
+                (Object::Struct(_), Object::Struct(_)) | (Object::Map(_), Object::Map(_))
+                    if deep =>
+                {
+                    previous
+                        .deep_merge(value)
+                        .expect("both sides are structs or maps")
+                }
+                _ => value,
+            };
         } else {
-            panic!()
+            merged.push((key, value));
         }
     }
+    entries
 }
 
 #[derive(Debug)]
 pub struct Field<'a>(pub Token<'a>, pub Expr<'a>);
 
+#[derive(Debug)]
+pub enum Pattern<'a> {
+    /// `Variant` or `Variant(binding)`
+    Variant(Token<'a>, Option<Token<'a>>),
+    /// `binding: Type`, narrows a union to one of its members.
+    Type(Token<'a>, Type),
+    Wildcard(Token<'a>),
+}
+
+#[derive(Debug)]
+pub struct Arm<'a>(pub Pattern<'a>, pub Expr<'a>);
+
 #[derive(Debug)]
 pub enum Expr<'a> {
-    Call(Box<Expr<'a>>, Vec<Expr<'a>>),
+    Call(Box<Expr<'a>>, Token<'a>, Vec<Expr<'a>>),
     Binary(Box<Expr<'a>>, Token<'a>, Box<Expr<'a>>),
-    Struct(Token<'a>, Vec<Field<'a>>),
+    /// `!value` or `-value`
+    Unary(Token<'a>, Box<Expr<'a>>),
+    /// Struct literal, with an optional `..base` to copy unspecified fields from.
+    Struct(Token<'a>, Option<Box<Expr<'a>>>, Vec<Field<'a>>),
+    /// Nested struct literal produced by dotted field paths (`a.b: value`).
+    Anonymous(Vec<Field<'a>>),
+    /// `Enum.Variant` or `Enum.Variant(payload)`
+    Variant(Token<'a>, Token<'a>, Option<Box<Expr<'a>>>),
+    Match(Token<'a>, Box<Expr<'a>>, Vec<Arm<'a>>),
+    /// `(x, y: Number) => body`, untyped parameters are inferred from the
+    /// expected function type.
+    Lambda(Vec<(Token<'a>, Option<Type>)>, Box<Expr<'a>>),
+    /// `{ 'key': value }`
+    Map(Token<'a>, Vec<(Expr<'a>, Expr<'a>)>),
+    /// `value[index]` on arrays and maps
+    Index(Box<Expr<'a>>, Token<'a>, Box<Expr<'a>>),
+    /// `for key, value in iterable { body }`, the key binding is optional.
+    For(
+        Token<'a>,
+        Option<Token<'a>>,
+        Token<'a>,
+        Box<Expr<'a>>,
+        Box<Expr<'a>>,
+    ),
     Grouping(Box<Expr<'a>>),
-    Array(Vec<Expr<'a>>),
-    Literal(Object),
+    Array(Token<'a>, Vec<Expr<'a>>),
+    Literal(Token<'a>, Object),
     Variable(Token<'a>),
 }
 
+impl<'a> Expr<'a> {
+    /// Token locating the expression.
+    pub fn token(&self) -> Option<&Token<'a>> {
+        match self {
+            Expr::Call(_, token, _)
+            | Expr::Binary(_, token, _)
+            | Expr::Unary(token, _)
+            | Expr::Struct(token, _, _)
+            | Expr::Variant(token, _, _)
+            | Expr::Match(token, _, _)
+            | Expr::Map(token, _)
+            | Expr::Index(_, token, _)
+            | Expr::For(token, _, _, _, _)
+            | Expr::Array(token, _)
+            | Expr::Literal(token, _)
+            | Expr::Variable(token) => Some(token),
+            Expr::Lambda(params, _) => params.first().map(|p| &p.0),
+            Expr::Anonymous(fields) => fields.first().map(|f| &f.0),
+            Expr::Grouping(expr) => expr.token(),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum BuiltinType {
     Unit,
     Num,
+    /// `Int` as written, the parser reads it as `Num` with an integer
+    /// constraint on struct fields.
+    Int,
     String,
+    Bool,
     Array,
+    Map,
 }
 
 impl BuiltinType {
@@ -89,8 +294,11 @@ impl BuiltinType {
         match self {
             BuiltinType::Unit => "Unit".to_string(),
             BuiltinType::Num => "Num".to_string(),
+            BuiltinType::Int => "Int".to_string(),
             BuiltinType::String => "String".to_string(),
+            BuiltinType::Bool => "Bool".to_string(),
             BuiltinType::Array => "Array".to_string(),
+            BuiltinType::Map => "Map".to_string(),
         }
     }
 }
@@ -101,10 +309,17 @@ pub struct QualifiedName(pub String);
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Type {
     Struct(Vec<FieldDeclaration>),
+    Enum(Vec<VariantDeclaration>),
     Nullable(Box<Type>),
-    Nested(Box<Type>, Box<Type>),
+    Nested(Box<Type>, Vec<Type>),
     Explicit(QualifiedName),
-    Fn(Box<Type>),
+    /// Type parameter of a generic declaration.
+    Param(QualifiedName),
+    /// Declaration with type parameters, instantiated on use.
+    Generic(Vec<QualifiedName>, Box<Type>),
+    Fn(Vec<Type>, Box<Type>),
+    /// `String | Number`
+    Union(Vec<Type>),
     // will always be a token of KIdentifier kind.
     Builtin(BuiltinType),
     Infer,
@@ -115,15 +330,66 @@ impl Type {
         match &*name.0 {
             "String" => Type::Builtin(BuiltinType::String),
             "Number" => Type::Builtin(BuiltinType::Num),
+            "Int" => Type::Builtin(BuiltinType::Int),
+            "Bool" => Type::Builtin(BuiltinType::Bool),
             "Array" => Type::Builtin(BuiltinType::Array),
+            "Map" => Type::Builtin(BuiltinType::Map),
             _ => Type::Explicit(name),
         }
     }
 
+    /// Replaces the names of `renamed`, pairs of old and new names.
+    pub fn rename(&mut self, renamed: &[(String, String)]) {
+        match self {
+            Type::Explicit(name) => {
+                if let Some((_, new)) = renamed.iter().find(|r| r.0 == name.0) {
+                    name.0 = new.clone();
+                }
+            }
+            Type::Struct(fields) => fields.iter_mut().for_each(|f| f.1.rename(renamed)),
+            Type::Enum(variants) => variants
+                .iter_mut()
+                .filter_map(|v| v.1.as_mut())
+                .for_each(|payload| payload.rename(renamed)),
+            Type::Nullable(ty) | Type::Generic(_, ty) => ty.rename(renamed),
+            Type::Nested(base, args) => {
+                base.rename(renamed);
+                args.iter_mut().for_each(|arg| arg.rename(renamed));
+            }
+            Type::Fn(params, ret) => {
+                params.iter_mut().for_each(|param| param.rename(renamed));
+                ret.rename(renamed);
+            }
+            Type::Union(members) => members.iter_mut().for_each(|m| m.rename(renamed)),
+            Type::Param(_) | Type::Builtin(_) | Type::Infer => {}
+        }
+    }
+
+    /// Kind of the values of the type, `named` gives the one of declared
+    /// names. None when the values have no single kind.
+    pub fn kind(&self, named: &dyn Fn(&QualifiedName) -> Option<Kind>) -> Option<Kind> {
+        match self {
+            Type::Builtin(BuiltinType::Num) => Some(Kind::Number),
+            Type::Builtin(BuiltinType::String) => Some(Kind::String),
+            Type::Builtin(BuiltinType::Bool) => Some(Kind::Bool),
+            Type::Builtin(BuiltinType::Array) => Some(Kind::Array),
+            Type::Builtin(BuiltinType::Map) => Some(Kind::Map),
+            Type::Builtin(BuiltinType::Unit) => Some(Kind::Nil),
+            Type::Nested(base, _) => base.kind(named),
+            Type::Generic(_, ty) => ty.kind(named),
+            Type::Struct(_) => Some(Kind::Struct),
+            Type::Enum(_) => Some(Kind::Variant),
+            Type::Fn(_, _) => Some(Kind::Fn),
+            Type::Explicit(name) => named(name),
+            _ => None,
+        }
+    }
+
     pub fn print(&self) -> String {
         match self {
-            Type::Fn(ty) => {
-                format!("(): {}", ty.print())
+            Type::Fn(args, ty) => {
+                let args: Vec<String> = args.iter().map(|a| a.print()).collect();
+                format!("Fn({}) -> {}", args.join(", "), ty.print())
             }
             Type::Struct(decls) => {
                 let mut s = "(".to_string();
@@ -136,9 +402,31 @@ impl Type {
                 s.push(')');
                 s
             }
+            Type::Enum(variants) => {
+                let variants: Vec<String> = variants
+                    .iter()
+                    .map(|v| match &v.1 {
+                        Some(ty) => format!("{}({})", v.0 .0, ty.print()),
+                        None => v.0 .0.clone(),
+                    })
+                    .collect();
+                variants.join(" | ")
+            }
             Type::Nullable(ty) => format!("{}?", ty.print()),
-            Type::Nested(ty, nested) => format!("{}<{}>", ty.print(), nested.print()),
+            Type::Nested(ty, nested) => {
+                let nested: Vec<String> = nested.iter().map(|n| n.print()).collect();
+                format!("{}<{}>", ty.print(), nested.join(", "))
+            }
             Type::Explicit(name) => name.0.clone(),
+            Type::Param(name) => name.0.clone(),
+            Type::Generic(params, ty) => {
+                let params: Vec<&str> = params.iter().map(|p| &*p.0).collect();
+                format!("<{}>{}", params.join(", "), ty.print())
+            }
+            Type::Union(options) => {
+                let options: Vec<String> = options.iter().map(|o| o.print()).collect();
+                options.join(" | ")
+            }
             Type::Builtin(builtin) => builtin.print(),
             Type::Infer => "_".to_string(),
         }
@@ -157,15 +445,23 @@ impl Type {
                 }
                 Type::Nested(base, n) => {
                     if let Type::Nested(other_base, other_n) = other_ty {
-                        base.can_be_inferred_from(other_base) && n.can_be_inferred_from(other_n)
+                        base.can_be_inferred_from(other_base)
+                            && n.len() == other_n.len()
+                            && n.iter()
+                                .zip(other_n)
+                                .all(|(n, other_n)| n.can_be_inferred_from(other_n))
                     } else {
                         false
                     }
                 }
                 Type::Explicit(_) => false,
+                Type::Param(_) => false,
+                Type::Generic(_, _) => false,
                 Type::Builtin(_) => false,
                 Type::Struct(_) => false,
-                Type::Fn(_) => false,
+                Type::Enum(_) => false,
+                Type::Fn(_, _) => false,
+                Type::Union(_) => false,
             }
         } else {
             true
@@ -173,17 +469,106 @@ impl Type {
     }
 }
 
+/// A field of a struct declaration, with its optional default value.
+#[derive(Clone, Debug)]
+pub struct FieldDeclaration(pub QualifiedName, pub Type, pub Option<Object>);
+
+impl FieldDeclaration {
+    pub fn new(name: QualifiedName, ty: Type) -> Self {
+        FieldDeclaration(name, ty, None)
+    }
+}
+
+// Default values do not change the shape of a struct, so they are ignored.
+impl PartialEq for FieldDeclaration {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0 && self.1 == other.1
+    }
+}
+
+impl Eq for FieldDeclaration {}
+
+/// Refinement of a struct field, checked whenever the struct is built. The
+/// token is the `where` or `matching` keyword.
+#[derive(Debug)]
+pub enum Constraint<'a> {
+    /// `field: Type where <condition on it>`
+    Where(Vec<QualifiedName>, Token<'a>, Expr<'a>),
+    /// `field: String matching '<regex>'`
+    Matching(Vec<QualifiedName>, Token<'a>, String),
+    /// `field: Int`, numbers without a fractional part.
+    Integer(Vec<QualifiedName>, Token<'a>),
+    /// Constraint of a declaration imported from a JSON Schema, with the
+    /// location of the property it comes from.
+    Imported(String, Box<Constraint<'a>>),
+}
+
+impl<'a> Constraint<'a> {
+    pub fn path(&self) -> &[QualifiedName] {
+        match self {
+            Constraint::Where(path, _, _)
+            | Constraint::Matching(path, _, _)
+            | Constraint::Integer(path, _) => path,
+            Constraint::Imported(_, constraint) => constraint.path(),
+        }
+    }
+
+    pub fn token(&self) -> Token<'a> {
+        match self {
+            Constraint::Where(_, token, _)
+            | Constraint::Matching(_, token, _)
+            | Constraint::Integer(_, token) => *token,
+            Constraint::Imported(_, constraint) => constraint.token(),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct FieldDeclaration(pub QualifiedName, pub Type); // Todo remove vec<Token> for a field and use anonymous struct instead.
+pub struct VariantDeclaration(pub QualifiedName, pub Option<Type>);
 
 #[derive(Debug)]
 pub enum Statement<'a> {
-    Variable(bool, Token<'a>, Option<Expr<'a>>),
-    Return(Expr<'a>),
+    Variable(bool, Token<'a>, Option<Type>, Option<Expr<'a>>),
+    Return(Token<'a>, Expr<'a>),
     Expr(Expr<'a>),
-    Struct(bool, Token<'a>, Vec<FieldDeclaration>),
+    Struct(
+        bool,
+        Token<'a>,
+        Vec<QualifiedName>,
+        Vec<FieldDeclaration>,
+        Vec<Constraint<'a>>,
+    ),
+    Enum(bool, Token<'a>, Vec<VariantDeclaration>),
+    /// `type Name = Type;`
+    Type(bool, Token<'a>, Type),
     Import(Token<'a>, Token<'a>),
-    Fn(bool, Token<'a>, Vec<(Token<'a>, Type)>, Type, Vec<Statement<'a>>),
+    /// `assert condition, 'message';`
+    Assert(Token<'a>, Expr<'a>, Option<Expr<'a>>),
+    Fn(
+        bool,
+        Token<'a>,
+        Vec<QualifiedName>,
+        Vec<(Token<'a>, Type)>,
+        Type,
+        Vec<Statement<'a>>,
+    ),
+}
+
+impl<'a> Statement<'a> {
+    /// Visibility and name of a declaration.
+    pub fn declared(&self) -> Option<(bool, &Token<'a>)> {
+        match self {
+            Statement::Variable(public, name, _, _)
+            | Statement::Struct(public, name, _, _, _)
+            | Statement::Enum(public, name, _)
+            | Statement::Type(public, name, _)
+            | Statement::Fn(public, name, _, _, _, _) => Some((*public, name)),
+            Statement::Return(_, _)
+            | Statement::Expr(_)
+            | Statement::Import(_, _)
+            | Statement::Assert(_, _, _) => None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -192,19 +577,19 @@ pub struct Program<'a>(pub Vec<Statement<'a>>);
 pub struct Parser<'a> {
     scanner: Scanner<'a>,
     current: Token<'a>,
-    debug: bool,
+    tracer: Tracer,
 }
 
 impl<'a> Parser<'a> {
-    pub fn init(scanner: Scanner<'a>, debug: bool, current: Token<'a>) -> Self {
+    pub fn init(scanner: Scanner<'a>, tracer: Tracer, current: Token<'a>) -> Self {
         Parser {
             scanner,
             current,
-            debug,
+            tracer,
         }
     }
 
-    pub fn parse(&mut self) -> Program {
+    pub fn parse(&mut self) -> Program<'a> {
         self.program()
     }
 
@@ -216,6 +601,16 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Matches a contextual keyword. `type` and `is` are not reserved, so they
+    /// remain usable as field names.
+    fn matches_word(&mut self, word: &str) -> Option<Token<'a>> {
+        if self.current.kind == TokenType::Identifier && self.current.lexeme == word {
+            Some(self.advance())
+        } else {
+            None
+        }
+    }
+
     fn advance(&mut self) -> Token<'a> {
         std::mem::replace(&mut self.current, self.scanner.scan_token())
     }
@@ -224,9 +619,9 @@ impl<'a> Parser<'a> {
         let mut stmts = vec![];
         while self.current.kind != TokenType::Eof {
             let statement = self.declaration();
-            if self.debug {
-                println!("[Parser] parsed {:?}", statement);
-            }
+            self.tracer.event(Phase::Parser, "statement", || {
+                vec![("statement", format!("{:?}", statement))]
+            });
             stmts.push(statement);
         }
         Program(stmts)
@@ -240,6 +635,10 @@ impl<'a> Parser<'a> {
                 self.let_declaration(true)
             } else if self.matches(TokenType::Struct).is_some() {
                 self.struct_declaration(true)
+            } else if self.matches(TokenType::Enum).is_some() {
+                self.enum_declaration(true)
+            } else if self.matches_word("type").is_some() {
+                self.type_declaration(true)
             } else {
                 panic!("the following declaration cannot be public.");
             }
@@ -249,35 +648,49 @@ impl<'a> Parser<'a> {
             self.import_declaration()
         } else if self.matches(TokenType::Struct).is_some() {
             self.struct_declaration(false)
-        } else if self.matches(TokenType::Return).is_some() {
-            self.return_declaration()
+        } else if self.matches(TokenType::Enum).is_some() {
+            self.enum_declaration(false)
+        } else if self.matches_word("type").is_some() {
+            self.type_declaration(false)
+        } else if let Some(token) = self.matches(TokenType::Return) {
+            self.return_declaration(token)
+        } else if let Some(token) = self.matches_word("assert") {
+            self.assert_declaration(token)
         } else {
             self.statement()
         }
     }
 
-    fn return_declaration(&mut self) -> Statement<'a> {
+    fn assert_declaration(&mut self, token: Token<'a>) -> Statement<'a> {
+        let condition = self.expression();
+        let message = self.matches(TokenType::Comma).map(|_| self.expression());
+        self.consume(TokenType::Semicolon, "Expect ';' after assertion.");
+        Statement::Assert(token, condition, message)
+    }
+
+    fn return_declaration(&mut self, token: Token<'a>) -> Statement<'a> {
         let value = self.expression();
 
         self.consume(TokenType::Semicolon, "Expected a ';' after return");
-        Statement::Return(value)
+        Statement::Return(token, value)
     }
 
     fn fn_declaration(&mut self, public: bool) -> Statement<'a> {
         let name = self.consume(TokenType::Identifier, "Expect function name.");
+        let params = self.type_parameters();
         self.consume(TokenType::LeftParen, "Expect '(' after function name.");
 
         let mut args = vec![];
         while self.matches(TokenType::RightParen).is_none() {
             let token = self.consume(TokenType::Identifier, "Expect argument name.");
             self.consume(TokenType::Colon, "Expect ':' after argument name.");
-            let ty = self.types();
+            let ty = bind_params(self.types(), &params);
             args.push((token, ty));
             self.matches(TokenType::Comma);
         }
 
         let return_type = if self.matches(TokenType::Colon).is_some() {
-            self.types()
+            bind_params(self.types(), &params)
         } else {
             Type::Builtin(BuiltinType::Unit)
         };
@@ -293,54 +706,158 @@ impl<'a> Parser<'a> {
             TokenType::RightBrace,
             "Expect '}' at the end of block declaration.",
         );
-        Statement::Fn(public, name, args, return_type, body)
+        Statement::Fn(public, name, params, args, return_type, body)
     }
 
     fn struct_declaration(&mut self, public: bool) -> Statement<'a> {
         let struct_name = self.consume(TokenType::KIdentifier, "Expect a name after struct.");
+        let params = self.type_parameters();
 
         self.consume(TokenType::LeftBrace, "Expect '{' after struct name.");
 
         let mut fields = vec![];
-        while self.current.kind != TokenType::RightBracket {
-            let key_name = QualifiedName(
-                self.consume(TokenType::Identifier, "Expect a field declaration.")
+        let mut constraints = vec![];
+        while self.current.kind != TokenType::RightBrace {
+            let line = self.current.line;
+            let mut path = vec![QualifiedName(
+                self.field_name("Expect a field declaration.")
                     .lexeme
                     .to_string(),
-            );
+            )];
             while self.matches(TokenType::Dot).is_some() {
-                self.consume(TokenType::Identifier, "Expect a nested field declaration.");
-                // TODO BETTER WORK BITCH
+                path.push(QualifiedName(
+                    self.field_name("Expect a nested field declaration.")
+                        .lexeme
+                        .to_string(),
+                ));
             }
 
             self.consume(TokenType::Colon, "Expect ':' after field declaration.");
 
-            let types = self.types();
-            fields.push(FieldDeclaration(key_name, types));
+            let type_name = self.current;
+            let written = bind_params(self.written_types(), &params);
+            let int = Type::Builtin(BuiltinType::Int);
+            if written == int || written == Type::Nullable(Box::new(int)) {
+                constraints.push(Constraint::Integer(path.clone(), type_name));
+            }
+            let types = numbers(written);
+            if let Some(token) = self.matches_word("where") {
+                constraints.push(Constraint::Where(path.clone(), token, self.expression()));
+            } else if let Some(token) = self.matches_word("matching") {
+                let pattern = self.consume(TokenType::String, "Expect a pattern after 'matching'.");
+                let pattern = pattern.lexeme[1..pattern.lexeme.len() - 1].to_string();
+                constraints.push(Constraint::Matching(path.clone(), token, pattern));
+            }
+            let default = if self.matches(TokenType::Equal).is_some() {
+                let line = self.current.line;
+                Some(constant(self.expression()).unwrap_or_else(|| {
+                    panic!(
+                        "Error at hello.m:{}: Default values must be constants.",
+                        line
+                    )
+                }))
+            } else {
+                None
+            };
+            insert_declaration(&mut fields, &path, line, types, default);
 
             self.matches(TokenType::Comma);
-            if self.current.kind == TokenType::RightBrace {
-                break;
-            }
         }
 
         self.consume(TokenType::RightBrace, "Expect '}' after struct definition.");
 
-        Statement::Struct(public, struct_name, fields)
+        Statement::Struct(public, struct_name, params, fields, constraints)
+    }
+
+    fn enum_declaration(&mut self, public: bool) -> Statement<'a> {
+        let enum_name = self.consume(TokenType::KIdentifier, "Expect a name after enum.");
+
+        self.consume(TokenType::LeftBrace, "Expect '{' after enum name.");
+
+        let mut variants = vec![];
+        while self.current.kind != TokenType::RightBrace {
+            let name = QualifiedName(
+                self.consume(TokenType::KIdentifier, "Expect a variant declaration.")
+                    .lexeme
+                    .to_string(),
+            );
+            let payload = if self.matches(TokenType::LeftParen).is_some() {
+                let ty = self.types();
+                self.consume(TokenType::RightParen, "Expect ')' after variant payload.");
+                Some(ty)
+            } else {
+                None
+            };
+            variants.push(VariantDeclaration(name, payload));
+
+            self.matches(TokenType::Comma);
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after enum definition.");
+
+        Statement::Enum(public, enum_name, variants)
+    }
+
+    fn type_declaration(&mut self, public: bool) -> Statement<'a> {
+        let name = self.consume(TokenType::KIdentifier, "Expect a name after type.");
+        self.consume(TokenType::Equal, "Expect '=' after type name.");
+        let ty = self.types();
+        self.consume(
+            TokenType::Semicolon,
+            "Expected a ';' after type declaration",
+        );
+        Statement::Type(public, name, ty)
+    }
+
+    /// Parses the optional `<A, B>` following the name of a declaration.
+    fn type_parameters(&mut self) -> Vec<QualifiedName> {
+        let mut params = vec![];
+        if self.matches(TokenType::LeftCaret).is_some() {
+            while self.matches(TokenType::RightCaret).is_none() {
+                params.push(QualifiedName(
+                    self.consume(TokenType::KIdentifier, "Expect a type parameter name.")
+                        .lexeme
+                        .to_string(),
+                ));
+                self.matches(TokenType::Comma);
+            }
+        }
+        params
     }
 
     fn types(&mut self) -> Type {
-        let mut base_type = Type::explicit(QualifiedName(
-            self.consume(
-                TokenType::KIdentifier,
-                "Expected types to start with a struct identifier.",
-            )
-            .lexeme
-            .to_string(),
-        ));
+        numbers(self.written_types())
+    }
+
+    /// Same as `types`, keeping a top-level `Int`.
+    fn written_types(&mut self) -> Type {
+        let ty = self.single_type();
+        if self.current.kind != TokenType::Pipe {
+            return ty;
+        }
+
+        let mut options = vec![ty];
+        while self.matches(TokenType::Pipe).is_some() {
+            options.push(self.single_type());
+        }
+        Type::Union(options)
+    }
+
+    fn single_type(&mut self) -> Type {
+        let name = self.consume(
+            TokenType::KIdentifier,
+            "Expected types to start with a struct identifier.",
+        );
+        if name.lexeme == "Fn" && self.matches(TokenType::LeftParen).is_some() {
+            return self.fn_type();
+        }
+        let mut base_type = Type::explicit(QualifiedName(name.lexeme.to_string()));
 
         if let Some(opening) = self.matches(TokenType::LeftCaret) {
-            let nested = self.types();
+            let mut nested = vec![self.types()];
+            while self.matches(TokenType::Comma).is_some() {
+                nested.push(self.types());
+            }
             self.consume(
                 TokenType::RightCaret,
                 format!(
@@ -348,7 +865,7 @@ impl<'a> Parser<'a> {
                     opening.line, opening.col
                 ),
             );
-            base_type = Type::Nested(Box::new(base_type), Box::new(nested))
+            base_type = Type::Nested(Box::new(base_type), nested)
         }
 
         if self.matches(TokenType::Question).is_some() {
@@ -358,8 +875,26 @@ impl<'a> Parser<'a> {
         base_type
     }
 
+    /// `Fn(A, B) -> R`, after the opening parenthesis.
+    fn fn_type(&mut self) -> Type {
+        let mut args = vec![];
+        while self.matches(TokenType::RightParen).is_none() {
+            args.push(self.types());
+            if self.current.kind != TokenType::RightParen {
+                self.consume(TokenType::Comma, "Expect ',' between argument types.");
+            }
+        }
+        self.consume(TokenType::Arrow, "Expect '->' after argument types.");
+        Type::Fn(args, Box::new(self.single_type()))
+    }
+
     fn let_declaration(&mut self, public: bool) -> Statement<'a> {
         let iden = self.consume(TokenType::Identifier, "Expected an identifier.");
+        let ty = if self.matches(TokenType::Colon).is_some() {
+            Some(self.types())
+        } else {
+            None
+        };
         let init = if self.matches(TokenType::Equal).is_some() {
             Some(self.expression())
         } else {
@@ -370,7 +905,7 @@ impl<'a> Parser<'a> {
             TokenType::Semicolon,
             "Expected a ';' after variable declaration",
         );
-        Statement::Variable(public, iden, init)
+        Statement::Variable(public, iden, ty, init)
     }
 
     fn import_declaration(&mut self) -> Statement<'a> {
@@ -393,9 +928,12 @@ impl<'a> Parser<'a> {
         if self.current.kind == kind {
             self.advance()
         } else {
-            if self.debug {
-                println!("Expected {:?}, got {:?}", kind, self.current.kind);
-            }
+            self.tracer.event(Phase::Parser, "unexpected", || {
+                vec![
+                    ("expected", format!("{:?}", kind)),
+                    ("got", format!("{:?}", self.current.kind)),
+                ]
+            });
             panic!(
                 "Error at hello.m:{}: {}",
                 self.current.line,
@@ -404,6 +942,21 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Field names that are not identifiers are quoted, `'runs-on'`. They
+    /// give an identifier token without the quotes.
+    fn field_name(&mut self, msg: &str) -> Token<'a> {
+        if self.current.kind == TokenType::String {
+            let token = self.advance();
+            Token {
+                kind: TokenType::Identifier,
+                lexeme: &token.lexeme[1..token.lexeme.len() - 1],
+                ..token
+            }
+        } else {
+            self.consume(TokenType::Identifier, msg)
+        }
+    }
+
     fn statement(&mut self) -> Statement<'a> {
         self.expr_statement()
     }
@@ -413,8 +966,108 @@ impl<'a> Parser<'a> {
     }
 
     fn expression(&mut self) -> Expr<'a> {
-        let expr = self.multiply();
-        if self.matches(TokenType::LeftParen).is_some() {
+        self.or()
+    }
+
+    fn or(&mut self) -> Expr<'a> {
+        let mut expr = self.and();
+        while let Some(op) = self.matches(TokenType::PipePipe) {
+            let value = self.and();
+            expr = Expr::Binary(Box::new(expr), op, Box::new(value));
+        }
+        expr
+    }
+
+    fn and(&mut self) -> Expr<'a> {
+        let mut expr = self.equality();
+        while let Some(op) = self.matches(TokenType::AndAnd) {
+            let value = self.equality();
+            expr = Expr::Binary(Box::new(expr), op, Box::new(value));
+        }
+        expr
+    }
+
+    fn equality(&mut self) -> Expr<'a> {
+        let mut expr = self.comparison();
+        while let Some(op) = self
+            .matches(TokenType::EqualEqual)
+            .or_else(|| self.matches(TokenType::BangEqual))
+        {
+            let value = self.comparison();
+            expr = Expr::Binary(Box::new(expr), op, Box::new(value));
+        }
+        expr
+    }
+
+    fn comparison(&mut self) -> Expr<'a> {
+        let mut expr = self.merge();
+        while let Some(op) = self
+            .matches(TokenType::LeftCaret)
+            .or_else(|| self.matches(TokenType::RightCaret))
+            .or_else(|| self.matches(TokenType::LessEqual))
+            .or_else(|| self.matches(TokenType::GreaterEqual))
+        {
+            let value = self.merge();
+            expr = Expr::Binary(Box::new(expr), op, Box::new(value));
+        }
+        expr
+    }
+
+    fn merge(&mut self) -> Expr<'a> {
+        let mut expr = self.add();
+        while let Some(op) = self
+            .matches(TokenType::SlashSlash)
+            .or_else(|| self.matches(TokenType::SlashBackslash))
+        {
+            let value = self.add();
+            expr = Expr::Binary(Box::new(expr), op, Box::new(value));
+        }
+        expr
+    }
+
+    fn add(&mut self) -> Expr<'a> {
+        let mut expr = self.multiply();
+        while let Some(op) = self.matches(TokenType::Plus) {
+            let value = self.multiply();
+            expr = Expr::Binary(Box::new(expr), op, Box::new(value));
+        }
+        expr
+    }
+
+    fn multiply(&mut self) -> Expr<'a> {
+        let mut expr = self.unary();
+        while let Some(op) = self.matches(TokenType::Star) {
+            let value = self.unary();
+            expr = Expr::Binary(Box::new(expr), op, Box::new(value));
+        }
+        expr
+    }
+
+    fn unary(&mut self) -> Expr<'a> {
+        if let Some(op) = self
+            .matches(TokenType::Bang)
+            .or_else(|| self.matches(TokenType::Minus))
+        {
+            Expr::Unary(op, Box::new(self.unary()))
+        } else {
+            self.call()
+        }
+    }
+
+    fn call(&mut self) -> Expr<'a> {
+        let mut expr = self.primary();
+        loop {
+            if let Some(bracket) = self.matches(TokenType::LeftBracket) {
+                let index = self.expression();
+                self.consume(TokenType::RightBracket, "Expect ']' after index.");
+                expr = Expr::Index(Box::new(expr), bracket, Box::new(index));
+                continue;
+            }
+            let paren = if let Some(paren) = self.matches(TokenType::LeftParen) {
+                paren
+            } else {
+                break;
+            };
             let mut args = vec![];
             while self.current.kind != TokenType::RightParen {
                 args.push(self.expression());
@@ -427,92 +1080,400 @@ impl<'a> Parser<'a> {
                 TokenType::RightParen,
                 "Expect ')' after function arguments.",
             );
-            Expr::Call(Box::new(expr), args)
-        } else {
-            expr
-        }
-    }
-
-    fn multiply(&mut self) -> Expr<'a> {
-        let operand = self.add();
-        if let Some(op) = self.matches(TokenType::Star) {
-            let value = self.expression();
-            Expr::Binary(Box::new(operand), op, Box::new(value))
-        } else {
-            operand
-        }
-    }
-
-    fn add(&mut self) -> Expr<'a> {
-        let operand = self.primary();
-        if let Some(op) = self.matches(TokenType::Plus) {
-            let value = self.expression();
-            Expr::Binary(Box::new(operand), op, Box::new(value))
-        } else {
-            operand
+            expr = Expr::Call(Box::new(expr), paren, args);
         }
+        expr
     }
 
     fn primary(&mut self) -> Expr<'a> {
         if let Some(identifier) = self.matches(TokenType::Identifier) {
             Expr::Variable(identifier)
         } else if let Some(num) = self.matches(TokenType::Number) {
-            Expr::Literal(Object::Num(f64::from_str(num.lexeme).unwrap()))
-        } else if self.matches(TokenType::Nil).is_some() {
-            Expr::Literal(Object::Nil)
+            Expr::Literal(num, Object::Num(f64::from_str(num.lexeme).unwrap()))
+        } else if let Some(nil) = self.matches(TokenType::Nil) {
+            Expr::Literal(nil, Object::Nil)
+        } else if let Some(token) = self.matches(TokenType::True) {
+            Expr::Literal(token, Object::Bool(true))
+        } else if let Some(token) = self.matches(TokenType::False) {
+            Expr::Literal(token, Object::Bool(false))
         } else if let Some(str) = self.matches(TokenType::String) {
-            Expr::Literal(Object::String(
-                str.lexeme[1..str.lexeme.len() - 1].to_string(),
-            ))
+            Expr::Literal(
+                str,
+                Object::String(str.lexeme[1..str.lexeme.len() - 1].into()),
+            )
         } else if let Some(token) = self.matches(TokenType::KIdentifier) {
-            self.structure(token)
+            if self.matches(TokenType::Dot).is_some() {
+                self.variant(token)
+            } else {
+                self.structure(token)
+            }
+        } else if let Some(token) = self.matches(TokenType::Match) {
+            self.match_expression(token)
+        } else if let Some(token) = self.matches(TokenType::If) {
+            self.if_expression(token)
         } else if self.matches(TokenType::LeftParen).is_some() {
-            let expr = self.expression();
-            self.consume(TokenType::RightParen, "Expect ')' after expression.");
-            Expr::Grouping(Box::new(expr))
-        } else if self.matches(TokenType::LeftBracket).is_some() {
-            self.array()
+            self.grouping_or_lambda()
+        } else if let Some(bracket) = self.matches(TokenType::LeftBracket) {
+            self.array(bracket)
+        } else if let Some(brace) = self.matches(TokenType::LeftBrace) {
+            self.map(brace)
+        } else if let Some(token) = self.matches(TokenType::For) {
+            self.comprehension(token)
         } else {
             panic!("Expected expression at hello.m:{}", self.current.line);
         }
     }
 
-    fn array(&mut self) -> Expr<'a> {
+    /// A parenthesis opens a lambda when the parameters are followed by `=>`.
+    fn grouping_or_lambda(&mut self) -> Expr<'a> {
+        if self.matches(TokenType::RightParen).is_some() {
+            return self.lambda(vec![]);
+        }
+
+        let expr = self.expression();
+        match expr {
+            Expr::Variable(param)
+                if self.current.kind == TokenType::Colon
+                    || self.current.kind == TokenType::Comma =>
+            {
+                let ty = self.matches(TokenType::Colon).map(|_| self.types());
+                let mut params = vec![(param, ty)];
+                while self.matches(TokenType::Comma).is_some() {
+                    let param = self.consume(TokenType::Identifier, "Expect a parameter name.");
+                    let ty = self.matches(TokenType::Colon).map(|_| self.types());
+                    params.push((param, ty));
+                }
+                self.consume(TokenType::RightParen, "Expect ')' after parameters.");
+                self.lambda(params)
+            }
+            expr => {
+                self.consume(TokenType::RightParen, "Expect ')' after expression.");
+                match expr {
+                    Expr::Variable(param) if self.current.kind == TokenType::FatArrow => {
+                        self.lambda(vec![(param, None)])
+                    }
+                    expr => Expr::Grouping(Box::new(expr)),
+                }
+            }
+        }
+    }
+
+    fn lambda(&mut self, params: Vec<(Token<'a>, Option<Type>)>) -> Expr<'a> {
+        self.consume(TokenType::FatArrow, "Expect '=>' after parameters.");
+        let body = self.expression();
+        Expr::Lambda(params, Box::new(body))
+    }
+
+    fn array(&mut self, bracket: Token<'a>) -> Expr<'a> {
         let mut exprs = vec![];
-        while self.matches(TokenType::RightBracket).is_none() {
+        while self.current.kind != TokenType::RightBracket {
             exprs.push(self.expression());
             if self.matches(TokenType::Comma).is_none() {
                 break;
             }
         }
         self.consume(TokenType::RightBracket, "Expect ']' after an array.");
-        Expr::Array(exprs)
+        Expr::Array(bracket, exprs)
+    }
+
+    fn map(&mut self, brace: Token<'a>) -> Expr<'a> {
+        let mut entries = vec![];
+        while self.current.kind != TokenType::RightBrace {
+            let key = self.expression();
+            self.consume(TokenType::Colon, "Expect ':' after map key.");
+            entries.push((key, self.expression()));
+            if self.matches(TokenType::Comma).is_none() {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after a map.");
+        Expr::Map(brace, entries)
+    }
+
+    fn comprehension(&mut self, token: Token<'a>) -> Expr<'a> {
+        let first = self.consume(TokenType::Identifier, "Expect a binding after 'for'.");
+        let (key, value) = if self.matches(TokenType::Comma).is_some() {
+            let value = self.consume(TokenType::Identifier, "Expect a value binding.");
+            (Some(first), value)
+        } else {
+            (None, first)
+        };
+        self.consume(TokenType::In, "Expect 'in' after loop bindings.");
+        let iterable = self.expression();
+        self.consume(TokenType::LeftBrace, "Expect '{' after loop iterable.");
+        let body = self.expression();
+        self.consume(TokenType::RightBrace, "Expect '}' after loop body.");
+        Expr::For(token, key, value, Box::new(iterable), Box::new(body))
+    }
+
+    fn variant(&mut self, token: Token<'a>) -> Expr<'a> {
+        let variant = self.consume(TokenType::KIdentifier, "Expect a variant after '.'.");
+        let payload = if self.matches(TokenType::LeftParen).is_some() {
+            let payload = self.expression();
+            self.consume(TokenType::RightParen, "Expect ')' after variant payload.");
+            Some(Box::new(payload))
+        } else {
+            None
+        };
+        Expr::Variant(token, variant, payload)
+    }
+
+    fn match_expression(&mut self, token: Token<'a>) -> Expr<'a> {
+        let value = self.expression();
+        self.consume(TokenType::LeftBrace, "Expect '{' after match value.");
+
+        let mut arms = vec![];
+        while self.current.kind != TokenType::RightBrace {
+            let pattern = if let Some(variant) = self.matches(TokenType::KIdentifier) {
+                let binding = if self.matches(TokenType::LeftParen).is_some() {
+                    let binding = self.consume(TokenType::Identifier, "Expect a binding name.");
+                    self.consume(TokenType::RightParen, "Expect ')' after binding.");
+                    Some(binding)
+                } else {
+                    None
+                };
+                Pattern::Variant(variant, binding)
+            } else {
+                let wildcard = self.consume(TokenType::Identifier, "Expect a pattern.");
+                if self.matches(TokenType::Colon).is_some() {
+                    Pattern::Type(wildcard, self.types())
+                } else if wildcard.lexeme != "_" {
+                    panic!(
+                        "Error at hello.m:{}: Expect a variant, a type or '_' pattern.",
+                        wildcard.line
+                    );
+                } else {
+                    Pattern::Wildcard(wildcard)
+                }
+            };
+
+            self.consume(TokenType::FatArrow, "Expect '=>' after pattern.");
+            arms.push(Arm(pattern, self.expression()));
+            self.matches(TokenType::Comma);
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after match arms.");
+        Expr::Match(token, Box::new(value), arms)
+    }
+
+    /// `if x is T { a } else { b }` is a match on `x`, which is narrowed to
+    /// `T` in the first branch.
+    fn if_expression(&mut self, token: Token<'a>) -> Expr<'a> {
+        let variable = self.consume(TokenType::Identifier, "Expect a variable after 'if'.");
+        if self.matches_word("is").is_none() {
+            panic!(
+                "Error at hello.m:{}: Expect 'is' after the variable.",
+                self.current.line
+            );
+        }
+        let ty = self.types();
+
+        self.consume(TokenType::LeftBrace, "Expect '{' after condition.");
+        let then = self.expression();
+        self.consume(TokenType::RightBrace, "Expect '}' after branch.");
+
+        let otherwise = self.consume(TokenType::Else, "Expect 'else' after branch.");
+        let otherwise_expr = if let Some(token) = self.matches(TokenType::If) {
+            self.if_expression(token)
+        } else {
+            self.consume(TokenType::LeftBrace, "Expect '{' after 'else'.");
+            let expr = self.expression();
+            self.consume(TokenType::RightBrace, "Expect '}' after branch.");
+            expr
+        };
+
+        Expr::Match(
+            token,
+            Box::new(Expr::Variable(variable)),
+            vec![
+                Arm(Pattern::Type(variable, ty), then),
+                Arm(Pattern::Wildcard(otherwise), otherwise_expr),
+            ],
+        )
     }
 
     fn structure(&mut self, token: Token<'a>) -> Expr<'a> {
         self.consume(TokenType::LeftBrace, "Expect '{' to instantiate a struct");
+        let base = if self.matches(TokenType::DotDot).is_some() {
+            let base = self.expression();
+            self.matches(TokenType::Comma);
+            Some(Box::new(base))
+        } else {
+            None
+        };
+
         let mut fields = vec![];
 
-        while self.current.kind != TokenType::RightBracket {
-            let key_name = self.consume(TokenType::Identifier, "Expect a field declaration.");
+        while self.current.kind != TokenType::RightBrace {
+            let mut path = vec![self.field_name("Expect a field declaration.")];
             while self.matches(TokenType::Dot).is_some() {
-                self.consume(TokenType::Identifier, "Expect a nested field declaration.");
-                // TODO BE SMART BITCH
+                path.push(self.field_name("Expect a nested field declaration."));
             }
 
             self.consume(TokenType::Colon, "Expect ':' after field declaration.");
 
-            fields.push(Field(key_name, self.expression()));
+            let value = self.expression();
+            insert_field(&mut fields, &path, value);
             self.matches(TokenType::Comma);
-            if self.current.kind == TokenType::RightBrace {
-                break;
-            }
         }
 
         self.consume(
             TokenType::RightBrace,
             "Expect '}' after struct instantiation",
         );
-        Expr::Struct(token, fields)
+        Expr::Struct(token, base, fields)
+    }
+}
+
+/// Parses the type of a native function, `<T>(Array<T>) -> T`.
+pub fn signature(source: &str) -> Type {
+    let mut scanner = Scanner::init(source, Tracer::default());
+    let current = scanner.scan_token();
+    let mut parser = Parser::init(scanner, Tracer::default(), current);
+    let params = parser.type_parameters();
+    parser.consume(TokenType::LeftParen, "Expect '(' in signature.");
+    let ty = bind_params(parser.fn_type(), &params);
+    if params.is_empty() {
+        ty
+    } else {
+        Type::Generic(params, Box::new(ty))
+    }
+}
+
+/// Inserts a declaration at the end of a dotted path, creating the anonymous
+/// struct types of the intermediate segments on the way.
+fn insert_declaration(
+    fields: &mut Vec<FieldDeclaration>,
+    path: &[QualifiedName],
+    line: usize,
+    ty: Type,
+    default: Option<Object>,
+) {
+    if let [name] = path {
+        fields.push(FieldDeclaration(name.clone(), ty, default));
+        return;
+    }
+
+    let i = if let Some(i) = fields.iter().position(|f| f.0 == path[0]) {
+        i
+    } else {
+        fields.push(FieldDeclaration::new(path[0].clone(), Type::Struct(vec![])));
+        fields.len() - 1
+    };
+
+    if let Type::Struct(nested) = &mut fields[i].1 {
+        insert_declaration(nested, &path[1..], line, ty, default);
+    } else {
+        not_nested(line, &path[0].0);
+    }
+}
+
+/// Same as `insert_declaration` for struct literals: `a.b: x, a.c: y` becomes
+/// `a: { b: x, c: y }`.
+fn insert_field<'a>(fields: &mut Vec<Field<'a>>, path: &[Token<'a>], value: Expr<'a>) {
+    if let [name] = path {
+        fields.push(Field(*name, value));
+        return;
+    }
+
+    let i = if let Some(i) = fields.iter().position(|f| f.0.lexeme == path[0].lexeme) {
+        i
+    } else {
+        fields.push(Field(path[0], Expr::Anonymous(vec![])));
+        fields.len() - 1
+    };
+
+    if let Expr::Anonymous(nested) = &mut fields[i].1 {
+        insert_field(nested, &path[1..], value);
+    } else {
+        not_nested(path[0].line, path[0].lexeme);
+    }
+}
+
+/// A dotted path goes through a field already holding something else:
+/// `meta: 'a', meta.ns: 'b'`.
+fn not_nested(line: usize, name: &str) -> ! {
+    eprintln!("hello.m:{}: Field '{}' is not a nested struct", line, name);
+    std::process::exit(324)
+}
+
+/// Turns the names of type parameters into `Type::Param` in a declared type.
+fn bind_params(ty: Type, params: &[QualifiedName]) -> Type {
+    match ty {
+        Type::Explicit(name) if params.contains(&name) => Type::Param(name),
+        Type::Nullable(ty) => Type::Nullable(Box::new(bind_params(*ty, params))),
+        Type::Nested(base, nested) => Type::Nested(
+            Box::new(bind_params(*base, params)),
+            nested.into_iter().map(|n| bind_params(n, params)).collect(),
+        ),
+        Type::Struct(fields) => Type::Struct(
+            fields
+                .into_iter()
+                .map(|f| FieldDeclaration(f.0, bind_params(f.1, params), f.2))
+                .collect(),
+        ),
+        Type::Union(options) => Type::Union(
+            options
+                .into_iter()
+                .map(|o| bind_params(o, params))
+                .collect(),
+        ),
+        Type::Fn(args, ret) => Type::Fn(
+            args.into_iter().map(|a| bind_params(a, params)).collect(),
+            Box::new(bind_params(*ret, params)),
+        ),
+        ty => ty,
+    }
+}
+
+/// Turns `Int` into `Num`.
+fn numbers(ty: Type) -> Type {
+    match ty {
+        Type::Builtin(BuiltinType::Int) => Type::Builtin(BuiltinType::Num),
+        Type::Nullable(ty) => Type::Nullable(Box::new(numbers(*ty))),
+        Type::Nested(base, nested) => Type::Nested(
+            Box::new(numbers(*base)),
+            nested.into_iter().map(numbers).collect(),
+        ),
+        Type::Struct(fields) => Type::Struct(
+            fields
+                .into_iter()
+                .map(|f| FieldDeclaration(f.0, numbers(f.1), f.2))
+                .collect(),
+        ),
+        Type::Union(options) => Type::Union(options.into_iter().map(numbers).collect()),
+        Type::Fn(args, ret) => Type::Fn(
+            args.into_iter().map(numbers).collect(),
+            Box::new(numbers(*ret)),
+        ),
+        ty => ty,
+    }
+}
+
+/// Evaluates literals, and arrays or maps of literals, the only expressions
+/// allowed as default values.
+fn constant(expr: Expr) -> Option<Object> {
+    match expr {
+        Expr::Literal(_, object) => Some(object),
+        Expr::Array(_, values) => values
+            .into_iter()
+            .map(constant)
+            .collect::<Option<Vec<_>>>()
+            .map(|items| Object::Array(items.into())),
+        Expr::Map(_, entries) => entries
+            .into_iter()
+            .map(|(key, value)| match constant(key) {
+                Some(Object::String(key)) => constant(value).map(|value| (key, value)),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(|entries| Object::Map(entries.into())),
+        Expr::Grouping(expr) => constant(*expr),
+        Expr::Unary(op, expr) if op.kind == TokenType::Minus => match constant(*expr) {
+            Some(Object::Num(n)) => Some(Object::Num(-n)),
+            _ => None,
+        },
+        Expr::Variant(_, variant, None) => Some(Object::Variant(variant.lexeme.into(), None)),
+        _ => None,
     }
 }
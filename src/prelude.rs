@@ -0,0 +1,389 @@
+//! Native functions, available in every module without an import. They are
+//! grouped in the `String`, `Number`, `Array`, `Encoding` and `Hash` modules,
+//! which `import` can name.
+use crate::encoding;
+use crate::parser::{signature, Object, Type};
+use crate::regex::Regex;
+use crate::vm::{mismatch, RuntimeError};
+use std::cmp::Ordering;
+use std::rc::Rc;
+use std::str::FromStr;
+
+/// Calls back into the VM, for natives taking a function.
+pub type Callback<'c> = &'c mut dyn FnMut(Object, Vec<Object>) -> Result<Object, RuntimeError>;
+
+pub type NativeFn = fn(Vec<Object>, Callback) -> Result<Object, RuntimeError>;
+
+#[derive(Debug)]
+pub struct Native {
+    pub name: &'static str,
+    pub module: &'static str,
+    /// Mia type of the function, with its type parameters.
+    pub signature: &'static str,
+    pub arity: u8,
+    pub fun: NativeFn,
+}
+
+impl Native {
+    pub fn ty(&self) -> Type {
+        signature(self.signature)
+    }
+}
+
+const fn native(
+    name: &'static str,
+    module: &'static str,
+    signature: &'static str,
+    arity: u8,
+    fun: NativeFn,
+) -> Native {
+    Native {
+        name,
+        module,
+        signature,
+        arity,
+        fun,
+    }
+}
+
+pub static NATIVES: &[Native] = &[
+    native(
+        "len",
+        "String",
+        "<T>(String | Array<T> | Map<String, T>) -> Number",
+        1,
+        len,
+    ),
+    native(
+        "join",
+        "String",
+        "(Array<String>, String) -> String",
+        2,
+        join,
+    ),
+    native(
+        "split",
+        "String",
+        "(String, String) -> Array<String>",
+        2,
+        split,
+    ),
+    native("upper", "String", "(String) -> String", 1, upper),
+    native("lower", "String", "(String) -> String", 1, lower),
+    native("trim", "String", "(String) -> String", 1, trim),
+    native(
+        "replace",
+        "String",
+        "(String, String, String) -> String",
+        3,
+        replace,
+    ),
+    native(
+        "startsWith",
+        "String",
+        "(String, String) -> Bool",
+        2,
+        starts_with,
+    ),
+    native("matches", "String", "(String, String) -> Bool", 2, matches),
+    native(
+        "toString",
+        "String",
+        "(String | Number | Bool) -> String",
+        1,
+        to_string,
+    ),
+    native(
+        "parseNumber",
+        "String",
+        "(String) -> Number?",
+        1,
+        parse_number,
+    ),
+    native("isInteger", "Number", "(Number) -> Bool", 1, is_integer),
+    native(
+        "concat",
+        "Array",
+        "<T>(Array<T>, Array<T>) -> Array<T>",
+        2,
+        concat,
+    ),
+    native(
+        "flatten",
+        "Array",
+        "<T>(Array<Array<T>>) -> Array<T>",
+        1,
+        flatten,
+    ),
+    native(
+        "range",
+        "Array",
+        "(Number, Number) -> Array<Number>",
+        2,
+        range,
+    ),
+    native(
+        "filter",
+        "Array",
+        "<T>(Array<T>, Fn(T) -> Bool) -> Array<T>",
+        2,
+        filter,
+    ),
+    native(
+        "map",
+        "Array",
+        "<T, U>(Array<T>, Fn(T) -> U) -> Array<U>",
+        2,
+        map,
+    ),
+    native("sort", "Array", "<T>(Array<T>) -> Array<T>", 1, sort),
+    native("unique", "Array", "<T>(Array<T>) -> Array<T>", 1, unique),
+    native(
+        "base64Encode",
+        "Encoding",
+        "(String) -> String",
+        1,
+        base64_encode,
+    ),
+    native(
+        "base64Decode",
+        "Encoding",
+        "(String) -> String?",
+        1,
+        base64_decode,
+    ),
+    native("hex", "Encoding", "(String) -> String", 1, hex),
+    native("urlEncode", "Encoding", "(String) -> String", 1, url_encode),
+    native("sha256", "Hash", "(String) -> String", 1, sha256),
+    native("uuid5", "Hash", "(String, String) -> String?", 2, uuid5),
+];
+
+pub fn find(name: &str) -> Option<&'static Native> {
+    NATIVES.iter().find(|n| n.name == name)
+}
+
+pub fn is_module(name: &str) -> bool {
+    NATIVES.iter().any(|n| n.module == name)
+}
+
+fn string(object: Object) -> Result<Rc<str>, RuntimeError> {
+    match object {
+        Object::String(s) => Ok(s),
+        object => Err(mismatch("String", &object)),
+    }
+}
+
+fn number(object: Object) -> Result<f64, RuntimeError> {
+    match object {
+        Object::Num(n) => Ok(n),
+        object => Err(mismatch("Number", &object)),
+    }
+}
+
+fn array(object: Object) -> Result<Vec<Object>, RuntimeError> {
+    match object {
+        Object::Array(items) => Ok(Rc::unwrap_or_clone(items)),
+        object => Err(mismatch("Array", &object)),
+    }
+}
+
+fn len(mut args: Vec<Object>, _: Callback) -> Result<Object, RuntimeError> {
+    let len = match args.remove(0) {
+        Object::String(s) => s.chars().count(),
+        Object::Array(items) => items.len(),
+        Object::Map(entries) => entries.len(),
+        object => return Err(mismatch("String", &object)),
+    };
+    Ok(Object::Num(len as f64))
+}
+
+fn join(mut args: Vec<Object>, _: Callback) -> Result<Object, RuntimeError> {
+    let separator = string(args.remove(1))?;
+    let items = array(args.remove(0))?
+        .into_iter()
+        .map(string)
+        .collect::<Result<Vec<Rc<str>>, RuntimeError>>()?;
+    Ok(Object::String(items.join(&separator).into()))
+}
+
+fn split(mut args: Vec<Object>, _: Callback) -> Result<Object, RuntimeError> {
+    let separator = string(args.remove(1))?;
+    let s = string(args.remove(0))?;
+    let parts: Vec<Object> = if separator.is_empty() {
+        s.chars()
+            .map(|c| Object::String(c.to_string().into()))
+            .collect()
+    } else {
+        s.split(&*separator)
+            .map(|part| Object::String(part.into()))
+            .collect()
+    };
+    Ok(Object::Array(parts.into()))
+}
+
+fn upper(mut args: Vec<Object>, _: Callback) -> Result<Object, RuntimeError> {
+    Ok(Object::String(
+        string(args.remove(0))?.to_uppercase().into(),
+    ))
+}
+
+fn lower(mut args: Vec<Object>, _: Callback) -> Result<Object, RuntimeError> {
+    Ok(Object::String(
+        string(args.remove(0))?.to_lowercase().into(),
+    ))
+}
+
+fn trim(mut args: Vec<Object>, _: Callback) -> Result<Object, RuntimeError> {
+    Ok(Object::String(string(args.remove(0))?.trim().into()))
+}
+
+fn replace(mut args: Vec<Object>, _: Callback) -> Result<Object, RuntimeError> {
+    let to = string(args.remove(2))?;
+    let from = string(args.remove(1))?;
+    Ok(Object::String(
+        string(args.remove(0))?.replace(&*from, &to).into(),
+    ))
+}
+
+fn starts_with(mut args: Vec<Object>, _: Callback) -> Result<Object, RuntimeError> {
+    let prefix = string(args.remove(1))?;
+    Ok(Object::Bool(string(args.remove(0))?.starts_with(&*prefix)))
+}
+
+/// Whether the pattern matches anywhere in the string.
+fn matches(mut args: Vec<Object>, _: Callback) -> Result<Object, RuntimeError> {
+    let pattern = string(args.remove(1))?;
+    let regex = Regex::new(&pattern).map_err(|reason| RuntimeError::InvalidPattern {
+        pattern: pattern.to_string(),
+        reason,
+    })?;
+    Ok(Object::Bool(regex.is_match(&string(args.remove(0))?)))
+}
+
+fn to_string(mut args: Vec<Object>, _: Callback) -> Result<Object, RuntimeError> {
+    Ok(match args.remove(0) {
+        Object::Num(n) => Object::String(n.to_string().into()),
+        Object::Bool(b) => Object::String(b.to_string().into()),
+        object => object,
+    })
+}
+
+fn parse_number(mut args: Vec<Object>, _: Callback) -> Result<Object, RuntimeError> {
+    Ok(f64::from_str(string(args.remove(0))?.trim())
+        .map(Object::Num)
+        .unwrap_or(Object::Nil))
+}
+
+/// Whether the number has no fractional part.
+fn is_integer(mut args: Vec<Object>, _: Callback) -> Result<Object, RuntimeError> {
+    Ok(Object::Bool(number(args.remove(0))?.fract() == 0.0))
+}
+
+fn concat(mut args: Vec<Object>, _: Callback) -> Result<Object, RuntimeError> {
+    let mut items = array(args.remove(0))?;
+    items.extend(array(args.remove(0))?);
+    Ok(Object::Array(items.into()))
+}
+
+fn flatten(mut args: Vec<Object>, _: Callback) -> Result<Object, RuntimeError> {
+    let mut items = vec![];
+    for item in array(args.remove(0))? {
+        items.extend(array(item)?);
+    }
+    Ok(Object::Array(items.into()))
+}
+
+/// Numbers from the start, included, to the end, excluded.
+fn range(mut args: Vec<Object>, _: Callback) -> Result<Object, RuntimeError> {
+    let end = number(args.remove(1))?;
+    let mut n = number(args.remove(0))?;
+    let mut items = vec![];
+    while n < end {
+        items.push(Object::Num(n));
+        n += 1.0;
+    }
+    Ok(Object::Array(items.into()))
+}
+
+fn filter(mut args: Vec<Object>, call: Callback) -> Result<Object, RuntimeError> {
+    let predicate = args.remove(1);
+    let mut items = vec![];
+    for item in array(args.remove(0))? {
+        if call(predicate.clone(), vec![item.clone()])? == Object::Bool(true) {
+            items.push(item);
+        }
+    }
+    Ok(Object::Array(items.into()))
+}
+
+fn map(mut args: Vec<Object>, call: Callback) -> Result<Object, RuntimeError> {
+    let f = args.remove(1);
+    let items = array(args.remove(0))?
+        .into_iter()
+        .map(|item| call(f.clone(), vec![item]))
+        .collect::<Result<Vec<Object>, RuntimeError>>()?;
+    Ok(Object::Array(items.into()))
+}
+
+/// Sorts numbers and strings, other values keep their order.
+fn sort(mut args: Vec<Object>, _: Callback) -> Result<Object, RuntimeError> {
+    let mut items = array(args.remove(0))?;
+    items.sort_by(|a, b| match (a, b) {
+        (Object::Num(a), Object::Num(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+        (Object::String(a), Object::String(b)) => a.cmp(b),
+        _ => Ordering::Equal,
+    });
+    Ok(Object::Array(items.into()))
+}
+
+/// Removes duplicates, keeping the first occurrence.
+fn unique(mut args: Vec<Object>, _: Callback) -> Result<Object, RuntimeError> {
+    let mut items: Vec<Object> = vec![];
+    for item in array(args.remove(0))? {
+        if !items.contains(&item) {
+            items.push(item);
+        }
+    }
+    Ok(Object::Array(items.into()))
+}
+
+fn base64_encode(mut args: Vec<Object>, _: Callback) -> Result<Object, RuntimeError> {
+    Ok(Object::String(
+        encoding::base64_encode(string(args.remove(0))?.as_bytes()).into(),
+    ))
+}
+
+/// Nil when the input is not base64, or does not decode to UTF-8.
+fn base64_decode(mut args: Vec<Object>, _: Callback) -> Result<Object, RuntimeError> {
+    Ok(encoding::base64_decode(&string(args.remove(0))?)
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .map(|s| Object::String(s.into()))
+        .unwrap_or(Object::Nil))
+}
+
+fn hex(mut args: Vec<Object>, _: Callback) -> Result<Object, RuntimeError> {
+    Ok(Object::String(
+        encoding::hex(string(args.remove(0))?.as_bytes()).into(),
+    ))
+}
+
+fn url_encode(mut args: Vec<Object>, _: Callback) -> Result<Object, RuntimeError> {
+    Ok(Object::String(
+        encoding::url_encode(&string(args.remove(0))?).into(),
+    ))
+}
+
+/// Hex digest of the UTF-8 bytes.
+fn sha256(mut args: Vec<Object>, _: Callback) -> Result<Object, RuntimeError> {
+    Ok(Object::String(
+        encoding::hex(&encoding::sha256(string(args.remove(0))?.as_bytes())).into(),
+    ))
+}
+
+/// Nil when the namespace is not a uuid.
+fn uuid5(mut args: Vec<Object>, _: Callback) -> Result<Object, RuntimeError> {
+    let name = string(args.remove(1))?;
+    Ok(encoding::uuid5(&string(args.remove(0))?, &name)
+        .map(|s| Object::String(s.into()))
+        .unwrap_or(Object::Nil))
+}
@@ -0,0 +1,257 @@
+//! Backtracking regular expressions, for `matching` constraints and the
+//! `matches` native. Supports classes, anchors, groups, alternations and the
+//! usual quantifiers.
+
+#[derive(Debug)]
+enum Node {
+    Char(char),
+    Any,
+    /// Ranges of characters, and whether the class is negated.
+    Class(Vec<(char, char)>, bool),
+    Start,
+    End,
+    Alternation(Vec<Vec<Node>>),
+    Repeat(Box<Node>, usize, Option<usize>),
+}
+
+#[derive(Debug)]
+pub struct Regex {
+    nodes: Vec<Node>,
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, String> {
+        let mut parser = RegexParser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+        };
+        let alternation = parser.alternation()?;
+        if parser.pos < parser.chars.len() {
+            return Err(format!("unmatched ')' at {}", parser.pos));
+        }
+        Ok(Regex {
+            nodes: vec![alternation],
+        })
+    }
+
+    /// Whether the pattern matches somewhere in `s`, use anchors to match all
+    /// of it.
+    pub fn is_match(&self, s: &str) -> bool {
+        let chars: Vec<char> = s.chars().collect();
+        (0..=chars.len()).any(|start| match_sequence(&self.nodes, &chars, start, &mut |_| true))
+    }
+}
+
+fn match_sequence(
+    nodes: &[Node],
+    chars: &[char],
+    pos: usize,
+    next: &mut dyn FnMut(usize) -> bool,
+) -> bool {
+    if let Some((node, rest)) = nodes.split_first() {
+        match_node(node, chars, pos, &mut |p| {
+            match_sequence(rest, chars, p, next)
+        })
+    } else {
+        next(pos)
+    }
+}
+
+fn match_node(
+    node: &Node,
+    chars: &[char],
+    pos: usize,
+    next: &mut dyn FnMut(usize) -> bool,
+) -> bool {
+    match node {
+        Node::Char(c) => chars.get(pos) == Some(c) && next(pos + 1),
+        Node::Any => pos < chars.len() && next(pos + 1),
+        Node::Class(ranges, negated) => match chars.get(pos) {
+            Some(c) => {
+                let within = ranges.iter().any(|(from, to)| from <= c && c <= to);
+                within != *negated && next(pos + 1)
+            }
+            None => false,
+        },
+        Node::Start => pos == 0 && next(pos),
+        Node::End => pos == chars.len() && next(pos),
+        Node::Alternation(alternatives) => alternatives
+            .iter()
+            .any(|alternative| match_sequence(alternative, chars, pos, next)),
+        Node::Repeat(node, min, max) => match_repeat(node, *min, *max, 0, chars, pos, next),
+    }
+}
+
+/// Greedy repetition, giving back one match at a time on failure.
+fn match_repeat(
+    node: &Node,
+    min: usize,
+    max: Option<usize>,
+    count: usize,
+    chars: &[char],
+    pos: usize,
+    next: &mut dyn FnMut(usize) -> bool,
+) -> bool {
+    if max.map(|max| count < max).unwrap_or(true)
+        && match_node(node, chars, pos, &mut |p| {
+            // An empty match would repeat forever.
+            p != pos && match_repeat(node, min, max, count + 1, chars, p, next)
+        })
+    {
+        return true;
+    }
+    count >= min && next(pos)
+}
+
+struct RegexParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl RegexParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    fn alternation(&mut self) -> Result<Node, String> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.peek() == Some('|') {
+            self.advance();
+            alternatives.push(self.sequence()?);
+        }
+        Ok(Node::Alternation(alternatives))
+    }
+
+    fn sequence(&mut self) -> Result<Vec<Node>, String> {
+        let mut nodes = vec![];
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantifier(atom)?);
+        }
+        Ok(nodes)
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        match self.advance() {
+            Some('(') => {
+                let group = self.alternation()?;
+                if self.advance() != Some(')') {
+                    return Err("missing ')'".to_string());
+                }
+                Ok(group)
+            }
+            Some('[') => self.class(),
+            Some('.') => Ok(Node::Any),
+            Some('^') => Ok(Node::Start),
+            Some('$') => Ok(Node::End),
+            Some('\\') => self.escape(),
+            Some(c @ ('*' | '+' | '?' | '{')) => Err(format!("nothing to repeat before '{}'", c)),
+            Some(c) => Ok(Node::Char(c)),
+            None => Err("unexpected end".to_string()),
+        }
+    }
+
+    fn escape(&mut self) -> Result<Node, String> {
+        let c = self.advance().ok_or("trailing '\\'")?;
+        Ok(match shorthand(c) {
+            Some((ranges, negated)) => Node::Class(ranges, negated),
+            None => Node::Char(c),
+        })
+    }
+
+    fn class(&mut self) -> Result<Node, String> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.advance();
+        }
+
+        let mut ranges = vec![];
+        loop {
+            let c = match self.advance() {
+                Some(']') if !ranges.is_empty() => break,
+                Some('\\') => {
+                    let c = self.advance().ok_or("trailing '\\'")?;
+                    if let Some((shorthand, false)) = shorthand(c) {
+                        ranges.extend(shorthand);
+                        continue;
+                    }
+                    c
+                }
+                Some(c) => c,
+                None => return Err("missing ']'".to_string()),
+            };
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1) != Some(&']') {
+                self.advance();
+                let to = self.advance().ok_or("missing ']'")?;
+                if to < c {
+                    return Err(format!("invalid range '{}-{}'", c, to));
+                }
+                ranges.push((c, to));
+            } else {
+                ranges.push((c, c));
+            }
+        }
+        Ok(Node::Class(ranges, negated))
+    }
+
+    fn quantifier(&mut self, atom: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => {
+                self.advance();
+                let min = self.number()?;
+                let max = if self.peek() == Some(',') {
+                    self.advance();
+                    if self.peek() == Some('}') {
+                        None
+                    } else {
+                        Some(self.number()?)
+                    }
+                } else {
+                    Some(min)
+                };
+                if self.peek() != Some('}') {
+                    return Err("missing '}'".to_string());
+                }
+                (min, max)
+            }
+            _ => return Ok(atom),
+        };
+        self.advance();
+        Ok(Node::Repeat(Box::new(atom), min, max))
+    }
+
+    fn number(&mut self) -> Result<usize, String> {
+        let start = self.pos;
+        while self.peek().map(|c| c.is_ascii_digit()).unwrap_or(false) {
+            self.advance();
+        }
+        self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .map_err(|_| "expected a number in a repetition".to_string())
+    }
+}
+
+/// `\d`, `\w`, `\s` and their negations.
+fn shorthand(c: char) -> Option<(Vec<(char, char)>, bool)> {
+    let ranges = match c.to_ascii_lowercase() {
+        'd' => vec![('0', '9')],
+        'w' => vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')],
+        's' => vec![(' ', ' '), ('\t', '\t'), ('\n', '\n'), ('\r', '\r')],
+        _ => return None,
+    };
+    Some((ranges, c.is_ascii_uppercase()))
+}
@@ -1,5 +1,6 @@
 use crate::token::Token;
 use crate::token::TokenType;
+use crate::trace::{Phase, Tracer};
 use std::str::Chars;
 
 pub struct Scanner<'a> {
@@ -9,11 +10,11 @@ pub struct Scanner<'a> {
     line: usize,
     col: usize,
     col_offset: usize,
-    debug: bool,
+    tracer: Tracer,
 }
 
 impl<'a> Scanner<'a> {
-    pub fn init(source: &'a str, debug: bool) -> Self {
+    pub fn init(source: &'a str, tracer: Tracer) -> Self {
         Scanner {
             iter: source.chars(),
             current: source,
@@ -21,12 +22,13 @@ impl<'a> Scanner<'a> {
             line: 1,
             col: 1,
             col_offset: 1,
-            debug,
+            tracer,
         }
     }
 
     pub fn scan_token(&mut self) -> Token<'a> {
         self.skip_whitespace();
+        self.col = self.col_offset;
         self.current = self.iter.as_str();
         self.offset = 0;
         let t = if let Some(c) = self.advance() {
@@ -42,19 +44,90 @@ impl<'a> Scanner<'a> {
                     ')' => self.make_token(TokenType::RightParen),
                     '{' => self.make_token(TokenType::LeftBrace),
                     '}' => self.make_token(TokenType::RightBrace),
-                    '<' => self.make_token(TokenType::LeftCaret),
-                    '>' => self.make_token(TokenType::RightCaret),
-                    '=' => self.make_token(TokenType::Equal),
-                    '&' => self.make_token(TokenType::Ampersand),
+                    '<' => {
+                        if self.peek() == Some('=') {
+                            self.advance();
+                            self.make_token(TokenType::LessEqual)
+                        } else {
+                            self.make_token(TokenType::LeftCaret)
+                        }
+                    }
+                    '>' => {
+                        if self.peek() == Some('=') {
+                            self.advance();
+                            self.make_token(TokenType::GreaterEqual)
+                        } else {
+                            self.make_token(TokenType::RightCaret)
+                        }
+                    }
+                    '=' => {
+                        if self.peek() == Some('>') {
+                            self.advance();
+                            self.make_token(TokenType::FatArrow)
+                        } else if self.peek() == Some('=') {
+                            self.advance();
+                            self.make_token(TokenType::EqualEqual)
+                        } else {
+                            self.make_token(TokenType::Equal)
+                        }
+                    }
+                    '!' => {
+                        if self.peek() == Some('=') {
+                            self.advance();
+                            self.make_token(TokenType::BangEqual)
+                        } else {
+                            self.make_token(TokenType::Bang)
+                        }
+                    }
+                    '&' => {
+                        if self.peek() == Some('&') {
+                            self.advance();
+                            self.make_token(TokenType::AndAnd)
+                        } else {
+                            self.make_token(TokenType::Ampersand)
+                        }
+                    }
                     '+' => self.make_token(TokenType::Plus),
-                    '-' => self.make_token(TokenType::Minus),
+                    '-' => {
+                        if self.peek() == Some('>') {
+                            self.advance();
+                            self.make_token(TokenType::Arrow)
+                        } else {
+                            self.make_token(TokenType::Minus)
+                        }
+                    }
                     '*' => self.make_token(TokenType::Star),
-                    '/' => self.make_token(TokenType::Slash),
-                    '.' => self.make_token(TokenType::Dot),
+                    '/' => {
+                        if self.peek() == Some('/') {
+                            self.advance();
+                            self.make_token(TokenType::SlashSlash)
+                        } else if self.peek() == Some('\\') {
+                            self.advance();
+                            self.make_token(TokenType::SlashBackslash)
+                        } else {
+                            self.make_token(TokenType::Slash)
+                        }
+                    }
+                    '.' => {
+                        if self.peek() == Some('.') {
+                            self.advance();
+                            self.make_token(TokenType::DotDot)
+                        } else {
+                            self.make_token(TokenType::Dot)
+                        }
+                    }
                     ';' => self.make_token(TokenType::Semicolon),
                     ',' => self.make_token(TokenType::Comma),
                     ':' => self.make_token(TokenType::Colon),
                     '?' => self.make_token(TokenType::Question),
+                    '|' => {
+                        if self.peek() == Some('|') {
+                            self.advance();
+                            self.make_token(TokenType::PipePipe)
+                        } else {
+                            self.make_token(TokenType::Pipe)
+                        }
+                    }
                     '\'' => self.string(),
                     _ => {
                         eprintln!("Unexpected character at line {}: {}", self.line, c);
@@ -66,9 +139,14 @@ impl<'a> Scanner<'a> {
             self.make_token(TokenType::Eof)
         };
 
-        if self.debug {
-            println!("[Scanner] {:?}", t);
-        }
+        self.tracer.event(Phase::Scanner, "token", || {
+            vec![
+                ("kind", format!("{:?}", t.kind)),
+                ("lexeme", t.lexeme.to_string()),
+                ("line", t.line.to_string()),
+                ("col", t.col.to_string()),
+            ]
+        });
 
         t
     }
@@ -77,11 +155,11 @@ impl<'a> Scanner<'a> {
         loop {
             if let Some(c) = self.peek() {
                 if c.is_whitespace() {
+                    self.advance();
                     if c == '\n' {
                         self.line += 1;
                         self.col_offset = 1;
                     }
-                    self.advance();
                 } else if c == '#' {
                     while self.peek() != Some('\n') {
                         self.advance();
@@ -97,11 +175,11 @@ impl<'a> Scanner<'a> {
 
     fn string(&mut self) -> Token<'a> {
         while self.peek() != Some('\'') {
-            if self.peek() == Some('\n') {
+            let c = self.advance();
+            if c == Some('\n') {
                 self.line += 1;
                 self.col_offset = 1;
             }
-            self.advance();
         }
 
         if self.is_at_end() {
@@ -133,6 +211,8 @@ impl<'a> Scanner<'a> {
                     TokenType::For
                 } else if local_iter.as_str() == "rom" {
                     TokenType::From
+                } else if local_iter.as_str() == "alse" {
+                    TokenType::False
                 } else {
                     TokenType::Identifier
                 }
@@ -148,6 +228,22 @@ impl<'a> Scanner<'a> {
                     TokenType::Identifier
                 }
             }
+            Some('e') => {
+                if local_iter.as_str() == "num" {
+                    TokenType::Enum
+                } else if local_iter.as_str() == "lse" {
+                    TokenType::Else
+                } else {
+                    TokenType::Identifier
+                }
+            }
+            Some('m') => {
+                if local_iter.as_str() == "atch" {
+                    TokenType::Match
+                } else {
+                    TokenType::Identifier
+                }
+            }
             Some('l') => {
                 if local_iter.as_str() == "et" {
                     TokenType::Let
@@ -162,6 +258,13 @@ impl<'a> Scanner<'a> {
                     TokenType::Identifier
                 }
             }
+            Some('t') => {
+                if local_iter.as_str() == "rue" {
+                    TokenType::True
+                } else {
+                    TokenType::Identifier
+                }
+            }
             Some('p') => {
                 if local_iter.as_str() == "ub" {
                     TokenType::Pub
@@ -231,26 +334,22 @@ impl<'a> Scanner<'a> {
     }
 
     fn make_token(&mut self, kind: TokenType) -> Token<'a> {
-        let t = Token {
+        Token {
             kind,
             lexeme: &self.current[0..self.offset],
             line: self.line,
             col: self.col,
             stack_offset: 0,
-        };
-        self.col = self.col_offset;
-        t
+        }
     }
 
     fn error_token(&mut self, msg: &'a str) -> Token<'a> {
-        let t = Token {
+        Token {
             kind: TokenType::Error,
             lexeme: msg,
             line: self.line,
             col: self.col,
             stack_offset: 0,
-        };
-        self.col = self.col_offset;
-        t
+        }
     }
 }
@@ -0,0 +1,1130 @@
+//! JSON Schema, drafts 7 and 2020-12, to and from Mia declarations.
+//!
+//! `import` backs `mia import-schema` and imports of `.json` modules. Objects
+//! with properties become structs, enums of capitalized names become enums,
+//! definitions become declarations named after their last segment. Bounds,
+//! lengths and patterns become field constraints. Properties that are not
+//! identifiers are quoted, the ones that cannot be quoted or whose type
+//! cannot be expressed are skipped with a comment.
+//!
+//! `export` backs `mia schema`, and goes the other way for the structs, enums
+//! and aliases of a module. Constraints are exported when they compare `it`,
+//! or its length, to a number, or match it against a pattern.
+use crate::formatter::EnumEncoding;
+use crate::json::Json;
+use crate::parser::{
+    BuiltinType, Constraint, Expr, FieldDeclaration, Object, Program, QualifiedName, Statement,
+    Type, VariantDeclaration,
+};
+use crate::regex::Regex;
+use crate::scanner::Scanner;
+use crate::token::{Token, TokenType};
+use crate::trace::Tracer;
+use std::rc::Rc;
+
+/// Mia declarations of a schema.
+pub struct Module {
+    pub source: String,
+    /// Struct and field names, with the JSON pointer of the property the
+    /// field is declared from.
+    pub origins: Vec<(String, String, String)>,
+}
+
+/// Declares the schema itself as `name`, unless it has a title, followed by
+/// its definitions.
+pub fn import(schema: &Json, name: &str) -> Module {
+    let mut importer = Importer {
+        root: schema,
+        references: vec![],
+        names: vec![],
+        enums: vec![],
+        declarations: vec![],
+        origins: vec![],
+    };
+
+    let name = schema
+        .get("title")
+        .and_then(Json::as_str)
+        .map(pascal_case)
+        .unwrap_or_else(|| pascal_case(name));
+    if is_declarable(schema) {
+        importer.names.push(name.clone());
+        importer
+            .references
+            .push(("#".to_string(), Some(name.clone())));
+        importer.declare(&name, schema);
+    }
+
+    for key in ["$defs", "definitions"] {
+        for (definition, _) in schema.get(key).map(Json::members).unwrap_or(&[]) {
+            let pointer = format!(
+                "#/{}/{}",
+                key,
+                definition.replace('~', "~0").replace('/', "~1")
+            );
+            importer.reference(&pointer);
+        }
+    }
+
+    Module {
+        source: importer.declarations.join("\n"),
+        origins: importer.origins,
+    }
+}
+
+/// A Mia type, as written in declarations.
+struct Ty {
+    text: String,
+    nullable: bool,
+}
+
+impl Ty {
+    fn new(text: &str) -> Self {
+        Ty {
+            text: text.to_string(),
+            nullable: false,
+        }
+    }
+
+    fn print(&self) -> String {
+        if self.nullable {
+            format!("{}?", self.text)
+        } else {
+            self.text.clone()
+        }
+    }
+}
+
+enum Condition {
+    Pattern(String),
+    Expr(String),
+}
+
+struct Importer<'s> {
+    root: &'s Json,
+    /// Declaration names of the `$ref` pointers, None for the ones that
+    /// cannot be expressed.
+    references: Vec<(String, Option<String>)>,
+    names: Vec<String>,
+    enums: Vec<String>,
+    declarations: Vec<String>,
+    origins: Vec<(String, String, String)>,
+}
+
+impl<'s> Importer<'s> {
+    /// Declares the referenced schema on first use. The name is reserved
+    /// before declaring, so recursive schemas refer to themselves.
+    fn reference(&mut self, pointer: &str) -> Option<String> {
+        if let Some((_, name)) = self.references.iter().find(|r| r.0 == pointer) {
+            return name.clone();
+        }
+        let schema = self.resolve_pointer(pointer)?;
+        let segment = pointer.rsplit('/').next().unwrap_or_default();
+        let segment = segment.replace("~1", "/").replace("~0", "~");
+        let name = self.unique(&pascal_case(segment.rsplit('.').next().unwrap_or_default()));
+        self.references
+            .push((pointer.to_string(), Some(name.clone())));
+        if !self.declare(&name, schema) {
+            if let Some(reference) = self.references.iter_mut().find(|r| r.0 == pointer) {
+                reference.1 = None;
+            }
+        }
+        self.references
+            .iter()
+            .find(|r| r.0 == pointer)
+            .and_then(|r| r.1.clone())
+    }
+
+    /// Pointers into this document, other documents are not followed.
+    fn resolve_pointer(&self, pointer: &str) -> Option<&'s Json> {
+        let path = pointer.strip_prefix('#')?;
+        let mut schema = self.root;
+        for segment in path.split('/').skip(1) {
+            let segment = segment.replace("~1", "/").replace("~0", "~");
+            schema = schema.get(&segment)?;
+        }
+        Some(schema)
+    }
+
+    /// Follows `$ref`s to the schema describing the value.
+    fn resolve(&self, mut schema: &'s Json) -> &'s Json {
+        for _ in 0..16 {
+            match schema
+                .get("$ref")
+                .and_then(Json::as_str)
+                .and_then(|r| self.resolve_pointer(r))
+            {
+                Some(target) => schema = target,
+                None => break,
+            }
+        }
+        schema
+    }
+
+    fn unique(&mut self, name: &str) -> String {
+        let mut unique = name.to_string();
+        let mut n = 2;
+        while self.names.contains(&unique) {
+            unique = format!("{}{}", name, n);
+            n += 1;
+        }
+        self.names.push(unique.clone());
+        unique
+    }
+
+    /// Declares the schema as a struct, an enum or an alias, false when its
+    /// type cannot be expressed.
+    fn declare(&mut self, name: &str, schema: &'s Json) -> bool {
+        if is_structure(schema) {
+            self.declare_struct(name, schema);
+        } else if let Some(variants) = variants(schema) {
+            self.declare_enum(name, &variants);
+        } else if let Some(ty) = self.type_of(schema, name) {
+            self.declarations
+                .push(format!("pub type {} = {};\n", name, ty.print()));
+        } else {
+            return false;
+        }
+        true
+    }
+
+    fn declare_enum(&mut self, name: &str, variants: &[&str]) {
+        let mut lines = vec![format!("pub enum {} {{", name)];
+        for variant in variants {
+            lines.push(format!("    {},", variant));
+        }
+        lines.push("}\n".to_string());
+        self.enums.push(name.to_string());
+        self.declarations.push(lines.join("\n"));
+    }
+
+    fn declare_struct(&mut self, name: &str, schema: &'s Json) {
+        let mut properties: Vec<&'s (String, Json)> = vec![];
+        let mut required: Vec<&'s str> = vec![];
+        let mut parts = vec![schema];
+        parts.extend(schema.get("allOf").and_then(Json::as_array).unwrap_or(&[]));
+        for part in parts {
+            let part = self.resolve(part);
+            properties.extend(part.get("properties").map(Json::members).unwrap_or(&[]));
+            required.extend(
+                part.get("required")
+                    .and_then(Json::as_array)
+                    .unwrap_or(&[])
+                    .iter()
+                    .filter_map(Json::as_str),
+            );
+        }
+
+        let mut lines = vec![format!("pub struct {} {{", name)];
+        for (key, property) in properties {
+            let field = if is_field_name(key) {
+                key.to_string()
+            } else if !key.is_empty() && !key.contains(['\'', '\n']) {
+                format!("'{}'", key)
+            } else {
+                lines.push(format!(
+                    "    # {:?} is skipped, it cannot be written as a field name.",
+                    key
+                ));
+                continue;
+            };
+            let ty = match self.type_of(property, &format!("{}{}", name, pascal_case(key))) {
+                Some(ty) => ty,
+                None => {
+                    lines.push(format!(
+                        "    # '{}' is skipped, its type cannot be expressed.",
+                        key
+                    ));
+                    continue;
+                }
+            };
+            let default = self.default(property, &ty);
+
+            let mut line = format!("    {}: {}", field, ty.text);
+            if ty.nullable || (!required.contains(&&**key) && default.is_none()) {
+                line.push('?');
+            }
+            let (constraint, skipped) = self.constraint(property);
+            for pattern in skipped {
+                lines.push(format!(
+                    "    # The pattern {:?} of '{}' is skipped, it cannot be expressed.",
+                    pattern, key
+                ));
+            }
+            if let Some(constraint) = constraint {
+                line.push(' ');
+                line.push_str(&constraint);
+            }
+            if let Some(default) = default {
+                line.push_str(" = ");
+                line.push_str(&default);
+            }
+            line.push(',');
+            lines.push(line);
+            if let Some(pointer) = pointer(self.root, property) {
+                self.origins
+                    .push((name.to_string(), key.to_string(), pointer));
+            }
+        }
+        lines.push("}\n".to_string());
+        self.declarations.push(lines.join("\n"));
+    }
+
+    /// Type of a value matching the schema, inline structs and enums are
+    /// declared under the hint.
+    fn type_of(&mut self, schema: &'s Json, hint: &str) -> Option<Ty> {
+        if let Some(pointer) = schema.get("$ref").and_then(Json::as_str) {
+            return self.reference(pointer).map(|name| Ty::new(&name));
+        }
+        if let Some(options) = schema
+            .get("oneOf")
+            .or_else(|| schema.get("anyOf"))
+            .and_then(Json::as_array)
+        {
+            return self.union(options, hint);
+        }
+        if is_structure(schema) {
+            let name = self.unique(hint);
+            self.declare_struct(&name, schema);
+            return Some(Ty::new(&name));
+        }
+        if let Some(parts) = schema.get("allOf").and_then(Json::as_array) {
+            if parts.len() == 1 {
+                return self.type_of(&parts[0], hint);
+            }
+        }
+        if variants(schema).is_some() {
+            let name = self.unique(hint);
+            self.declare(&name, schema);
+            return Some(Ty::new(&name));
+        }
+        if let Some(values) = schema.get("enum").and_then(Json::as_array) {
+            return literal_type(values);
+        }
+        if let Some(value) = schema.get("const") {
+            return literal_type(std::slice::from_ref(value));
+        }
+
+        let types: Vec<&str> = match schema.get("type") {
+            Some(Json::String(ty)) => vec![ty],
+            Some(Json::Array(types)) => types.iter().filter_map(Json::as_str).collect(),
+            _ if schema.get("items").is_some() => vec!["array"],
+            _ => return None,
+        };
+        let mut members = vec![];
+        for ty in types.iter().filter(|ty| **ty != "null") {
+            let member = match *ty {
+                "string" => Ty::new("String"),
+                "integer" => Ty::new("Int"),
+                "number" => Ty::new("Number"),
+                "boolean" => Ty::new("Bool"),
+                "array" => {
+                    let items = schema.get("items")?;
+                    let item = self.type_of(items, &format!("{}Item", hint))?;
+                    Ty::new(&format!("Array<{}>", item.print()))
+                }
+                "object" => match schema.get("additionalProperties") {
+                    Some(values @ Json::Object(_)) => {
+                        let value = self.type_of(values, &format!("{}Value", hint))?;
+                        Ty::new(&format!("Map<String, {}>", value.print()))
+                    }
+                    _ => return None,
+                },
+                _ => return None,
+            };
+            members.push(member.print());
+        }
+        if members.is_empty() {
+            return None;
+        }
+        Some(Ty {
+            text: members.join(" | "),
+            nullable: types.contains(&"null"),
+        })
+    }
+
+    fn union(&mut self, options: &'s [Json], hint: &str) -> Option<Ty> {
+        let mut members: Vec<String> = vec![];
+        let mut nullable = false;
+        for (i, option) in options.iter().enumerate() {
+            if option.get("type").and_then(Json::as_str) == Some("null") {
+                nullable = true;
+                continue;
+            }
+            let member = self.type_of(option, &format!("{}{}", hint, i + 1))?;
+            nullable |= member.nullable;
+            if !members.contains(&member.text) {
+                members.push(member.text);
+            }
+        }
+        if members.is_empty() {
+            return None;
+        }
+        Some(Ty {
+            text: members.join(" | "),
+            nullable,
+        })
+    }
+
+    /// Defaults of scalars and enums, other defaults are left out.
+    fn default(&self, schema: &Json, ty: &Ty) -> Option<String> {
+        match (schema.get("default")?, &*ty.text) {
+            (Json::String(s), "String") if !s.contains('\'') => Some(format!("'{}'", s)),
+            (Json::String(s), name) if self.enums.iter().any(|e| e == name) && is_variant(s) => {
+                Some(format!("{}.{}", name, s))
+            }
+            (Json::Number(n), "Number" | "Int") => Some(number(*n)),
+            (Json::Bool(b), "Bool") => Some(b.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Bounds, lengths and patterns of strings, numbers and arrays, followed
+    /// by the patterns that cannot be expressed.
+    fn constraint(&self, schema: &Json) -> (Option<String>, Vec<String>) {
+        let schema = self.resolve(schema);
+        let bound = |key: &str, op: &str| {
+            schema
+                .get(key)
+                .and_then(Json::as_f64)
+                .map(|n| Condition::Expr(format!("it {} {}", op, number(n))))
+        };
+        let length = |key: &str, op: &str| {
+            schema
+                .get(key)
+                .and_then(Json::as_f64)
+                .map(|n| Condition::Expr(format!("len(it) {} {}", op, number(n))))
+        };
+
+        let ty = match schema.get("type").and_then(Json::as_str) {
+            Some(ty) => ty,
+            None => return (None, vec![]),
+        };
+        let conditions: Vec<Condition> = match ty {
+            "string" => {
+                let values = schema
+                    .get("enum")
+                    .and_then(Json::as_array)
+                    .filter(|_| variants(schema).is_none())
+                    .and_then(|values| values.iter().map(Json::as_str).collect::<Option<Vec<_>>>())
+                    .map(|values| {
+                        let escaped: Vec<String> = values.iter().map(|v| escape(v)).collect();
+                        Condition::Pattern(format!("^({})$", escaped.join("|")))
+                    });
+                let pattern = schema
+                    .get("pattern")
+                    .and_then(Json::as_str)
+                    .map(|p| Condition::Pattern(p.to_string()));
+                vec![
+                    values,
+                    pattern,
+                    length("minLength", ">="),
+                    length("maxLength", "<="),
+                ]
+                .into_iter()
+                .flatten()
+                .collect()
+            }
+            "number" | "integer" => vec![
+                bound("minimum", ">="),
+                bound("maximum", "<="),
+                bound("exclusiveMinimum", ">"),
+                bound("exclusiveMaximum", "<"),
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+            "array" => vec![length("minItems", ">="), length("maxItems", "<=")]
+                .into_iter()
+                .flatten()
+                .collect(),
+            _ => vec![],
+        };
+
+        // Mia strings have no escapes, and patterns must be understood by the
+        // regex engine of `matching`.
+        let (conditions, skipped): (Vec<Condition>, Vec<Condition>) =
+            conditions.into_iter().partition(|c| match c {
+                Condition::Pattern(p) => !p.contains('\'') && Regex::new(p).is_ok(),
+                Condition::Expr(_) => true,
+            });
+        let skipped = skipped
+            .into_iter()
+            .map(|c| match c {
+                Condition::Pattern(p) | Condition::Expr(p) => p,
+            })
+            .collect();
+        let constraint = match &conditions[..] {
+            [] => None,
+            [Condition::Pattern(pattern)] => Some(format!("matching '{}'", pattern)),
+            conditions => {
+                let conditions: Vec<String> = conditions
+                    .iter()
+                    .map(|c| match c {
+                        Condition::Pattern(p) => format!("matches(it, '{}')", p),
+                        Condition::Expr(e) => e.clone(),
+                    })
+                    .collect();
+                Some(format!("where {}", conditions.join(" && ")))
+            }
+        };
+        (constraint, skipped)
+    }
+}
+
+/// Whether the root schema describes a value, rather than only holding
+/// definitions.
+fn is_declarable(schema: &Json) -> bool {
+    [
+        "type",
+        "properties",
+        "$ref",
+        "oneOf",
+        "anyOf",
+        "allOf",
+        "enum",
+        "const",
+    ]
+    .iter()
+    .any(|key| schema.get(key).is_some())
+}
+
+/// Objects with properties, or combining several schemas.
+fn is_structure(schema: &Json) -> bool {
+    let object = matches!(schema.get("type"), None | Some(Json::String(_)))
+        && schema
+            .get("type")
+            .and_then(Json::as_str)
+            .unwrap_or("object")
+            == "object";
+    let properties = !schema
+        .get("properties")
+        .map(Json::members)
+        .unwrap_or(&[])
+        .is_empty();
+    let parts = schema
+        .get("allOf")
+        .and_then(Json::as_array)
+        .map(|parts| parts.len() > 1)
+        .unwrap_or(false);
+    object && (properties || parts)
+}
+
+/// Values of a string enum that can all be written as variants.
+fn variants(schema: &Json) -> Option<Vec<&str>> {
+    let values = schema.get("enum").and_then(Json::as_array)?;
+    let variants = values
+        .iter()
+        .map(Json::as_str)
+        .collect::<Option<Vec<_>>>()?;
+    if variants.iter().all(|v| is_variant(v)) {
+        Some(variants)
+    } else {
+        None
+    }
+}
+
+/// Union of the kinds of literal values.
+fn literal_type(values: &[Json]) -> Option<Ty> {
+    let mut members: Vec<&str> = vec![];
+    let mut nullable = false;
+    for value in values {
+        let member = match value {
+            Json::String(_) => "String",
+            Json::Number(_) => "Number",
+            Json::Bool(_) => "Bool",
+            Json::Null => {
+                nullable = true;
+                continue;
+            }
+            _ => return None,
+        };
+        if !members.contains(&member) {
+            members.push(member);
+        }
+    }
+    if members.is_empty() {
+        return None;
+    }
+    Some(Ty {
+        text: members.join(" | "),
+        nullable,
+    })
+}
+
+/// JSON pointer of a schema inside the document, `#/properties/replicas`.
+fn pointer(root: &Json, target: &Json) -> Option<String> {
+    if std::ptr::eq(root, target) {
+        return Some("#".to_string());
+    }
+    let children: Vec<(String, &Json)> = match root {
+        Json::Object(members) => members.iter().map(|(k, v)| (k.clone(), v)).collect(),
+        Json::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| (i.to_string(), item))
+            .collect(),
+        _ => vec![],
+    };
+    children.into_iter().find_map(|(segment, child)| {
+        let rest = pointer(child, target)?;
+        let segment = segment.replace('~', "~0").replace('/', "~1");
+        Some(format!("#/{}{}", segment, &rest[1..]))
+    })
+}
+
+/// Whether the name is a single token of the kind, keywords are not.
+fn scans_as(name: &str, kind: TokenType) -> bool {
+    if !name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        || !name.chars().all(|c| c.is_alphanumeric() || c == '_')
+    {
+        return false;
+    }
+    let token = Scanner::init(name, Tracer::default()).scan_token();
+    token.kind == kind && token.lexeme == name
+}
+
+fn is_field_name(name: &str) -> bool {
+    scans_as(name, TokenType::Identifier)
+}
+
+fn is_variant(name: &str) -> bool {
+    scans_as(name, TokenType::KIdentifier)
+}
+
+/// `io.k8s-api_v1` gives `IoK8sApiV1`.
+pub fn pascal_case(name: &str) -> String {
+    let mut pascal = String::new();
+    for part in name.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            pascal.push(first.to_ascii_uppercase());
+            pascal.push_str(chars.as_str());
+        }
+    }
+    if !pascal.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        pascal.insert_str(0, "Schema");
+    }
+    pascal
+}
+
+fn number(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        format!("{}", n as i64)
+    } else {
+        n.to_string()
+    }
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        if ".^$|()[]{}*+?\\".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Schema of the module, defining each of its declarations. The root is the
+/// type of `main`, when it is declared or built from a struct literal.
+pub fn export(program: &Program, enum_encoding: EnumEncoding) -> Object {
+    let exporter = Exporter {
+        program,
+        enum_encoding,
+    };
+
+    let mut definitions = vec![];
+    for statement in &program.0 {
+        let definition = match statement {
+            Statement::Struct(_, name, params, fields, constraints) => {
+                let bindings: Vec<(QualifiedName, Object)> =
+                    params.iter().map(|p| (p.clone(), map(vec![]))).collect();
+                let constraints: Vec<(&[QualifiedName], &Constraint)> =
+                    constraints.iter().map(|c| (c.path(), c)).collect();
+                (name, exporter.structure(fields, &constraints, &bindings))
+            }
+            Statement::Enum(_, name, variants) => (name, exporter.enumeration(variants)),
+            Statement::Type(_, name, ty) => (name, exporter.schema(ty, &[])),
+            _ => continue,
+        };
+        definitions.push((definition.0.lexeme.into(), definition.1));
+    }
+
+    let mut schema = vec![(
+        "$schema".into(),
+        string("https://json-schema.org/draft/2020-12/schema"),
+    )];
+    let root = program.0.iter().find_map(|statement| match statement {
+        Statement::Variable(true, name, Some(ty), _) if name.lexeme == "main" => {
+            Some(exporter.schema(ty, &[]))
+        }
+        Statement::Variable(true, name, None, Some(Expr::Struct(token, _, _)))
+            if name.lexeme == "main" =>
+        {
+            Some(reference(token.lexeme))
+        }
+        _ => None,
+    });
+    if let Some(Object::Map(root)) = root {
+        schema.extend(Rc::unwrap_or_clone(root));
+    }
+    schema.push(("$defs".into(), Object::Map(definitions.into())));
+    Object::Map(schema.into())
+}
+
+struct Exporter<'p, 'a> {
+    program: &'p Program<'a>,
+    enum_encoding: EnumEncoding,
+}
+
+impl<'p, 'a> Exporter<'p, 'a> {
+    /// Type parameters are bound to the schemas of their arguments.
+    fn schema(&self, ty: &Type, bindings: &[(QualifiedName, Object)]) -> Object {
+        match ty {
+            Type::Builtin(BuiltinType::Num | BuiltinType::Int) => kind("number"),
+            Type::Builtin(BuiltinType::String) => kind("string"),
+            Type::Builtin(BuiltinType::Bool) => kind("boolean"),
+            Type::Builtin(BuiltinType::Unit) => kind("null"),
+            Type::Builtin(BuiltinType::Array) => kind("array"),
+            Type::Builtin(BuiltinType::Map) => kind("object"),
+            Type::Nested(base, args) => match (&**base, &args[..]) {
+                (Type::Builtin(BuiltinType::Array), [item]) => map(vec![
+                    ("type", string("array")),
+                    ("items", self.schema(item, bindings)),
+                ]),
+                (Type::Builtin(BuiltinType::Map), [_, value]) => map(vec![
+                    ("type", string("object")),
+                    ("additionalProperties", self.schema(value, bindings)),
+                ]),
+                (Type::Explicit(name), args) => self.instance(name, args, bindings),
+                _ => map(vec![]),
+            },
+            Type::Nullable(ty) => nullable(self.schema(ty, bindings)),
+            Type::Explicit(name) | Type::Param(name) => {
+                if let Some(binding) = bindings.iter().find(|b| &b.0 == name) {
+                    binding.1.clone()
+                } else if self.declaration(name).is_some() {
+                    reference(&name.0)
+                } else {
+                    map(vec![])
+                }
+            }
+            Type::Struct(fields) => self.structure(fields, &[], bindings),
+            Type::Union(members) => map(vec![(
+                "anyOf",
+                Object::Array(
+                    members
+                        .iter()
+                        .map(|m| self.schema(m, bindings))
+                        .collect::<Vec<_>>()
+                        .into(),
+                ),
+            )]),
+            // Functions are not data.
+            Type::Generic(_, _) | Type::Fn(_, _) | Type::Enum(_) | Type::Infer => map(vec![]),
+        }
+    }
+
+    fn declaration(&self, name: &QualifiedName) -> Option<&'p Statement<'a>> {
+        self.program.0.iter().find(|statement| match statement {
+            Statement::Struct(_, token, _, _, _)
+            | Statement::Enum(_, token, _)
+            | Statement::Type(_, token, _) => token.lexeme == name.0,
+            _ => false,
+        })
+    }
+
+    /// JSON Schema has no generics, instances of generic structs are inlined.
+    fn instance(
+        &self,
+        name: &QualifiedName,
+        args: &[Type],
+        bindings: &[(QualifiedName, Object)],
+    ) -> Object {
+        match self.declaration(name) {
+            Some(Statement::Struct(_, _, params, fields, constraints)) => {
+                let bindings: Vec<(QualifiedName, Object)> = params
+                    .iter()
+                    .zip(args)
+                    .map(|(param, arg)| (param.clone(), self.schema(arg, bindings)))
+                    .collect();
+                let constraints: Vec<(&[QualifiedName], &Constraint)> =
+                    constraints.iter().map(|c| (c.path(), c)).collect();
+                self.structure(fields, &constraints, &bindings)
+            }
+            Some(_) => reference(&name.0),
+            None => map(vec![]),
+        }
+    }
+
+    /// Fields are required unless they can be omitted from literals.
+    /// Constraint paths are relative to these fields.
+    fn structure(
+        &self,
+        fields: &[FieldDeclaration],
+        constraints: &[(&[QualifiedName], &Constraint)],
+        bindings: &[(QualifiedName, Object)],
+    ) -> Object {
+        let mut properties = vec![];
+        let mut required = vec![];
+        for FieldDeclaration(name, ty, default) in fields {
+            let mut schema = match ty {
+                Type::Struct(nested) => {
+                    let nested_constraints: Vec<(&[QualifiedName], &Constraint)> = constraints
+                        .iter()
+                        .filter(|(path, _)| path.len() > 1 && path[0] == *name)
+                        .map(|(path, c)| (&path[1..], *c))
+                        .collect();
+                    self.structure(nested, &nested_constraints, bindings)
+                }
+                ty => self.schema(ty, bindings),
+            };
+            if let Object::Map(keywords) = &mut schema {
+                let keywords = Rc::make_mut(keywords);
+                if let Some(default) = default {
+                    keywords.push(("default".into(), default.clone()));
+                }
+                for (_, constraint) in constraints
+                    .iter()
+                    .filter(|(path, _)| *path == std::slice::from_ref(name))
+                {
+                    self.keywords(ty, constraint, keywords);
+                }
+            }
+            if !self.is_optional(ty, default) {
+                required.push(string(&name.0));
+            }
+            properties.push((name.0.as_str().into(), schema));
+        }
+
+        map(vec![
+            ("type", string("object")),
+            ("properties", Object::Map(properties.into())),
+            ("required", Object::Array(required.into())),
+            ("additionalProperties", Object::Bool(false)),
+        ])
+    }
+
+    /// Same as the type checker, a field can be omitted when it has a
+    /// default, is nullable, or is a struct whose fields can all be omitted.
+    fn is_optional(&self, ty: &Type, default: &Option<Object>) -> bool {
+        let all_optional =
+            |fields: &[FieldDeclaration]| fields.iter().all(|f| self.is_optional(&f.1, &f.2));
+        default.is_some()
+            || match ty {
+                Type::Nullable(_) => true,
+                Type::Struct(fields) => all_optional(fields),
+                Type::Explicit(name) => match self.declaration(name) {
+                    Some(Statement::Struct(_, _, _, fields, _)) => all_optional(fields),
+                    Some(Statement::Type(_, _, aliased)) => self.is_optional(aliased, &None),
+                    _ => false,
+                },
+                _ => false,
+            }
+    }
+
+    /// Written as the formatter writes variants with `enum_encoding`.
+    fn enumeration(&self, variants: &[VariantDeclaration]) -> Object {
+        let options: Vec<Object> = variants
+            .iter()
+            .map(
+                |VariantDeclaration(name, payload)| match (self.enum_encoding, payload) {
+                    (EnumEncoding::Untagged, Some(payload)) => self.schema(payload, &[]),
+                    (EnumEncoding::String | EnumEncoding::Untagged, None) => {
+                        map(vec![("const", string(&name.0))])
+                    }
+                    (_, payload) => {
+                        let mut properties = vec![("tag", map(vec![("const", string(&name.0))]))];
+                        let mut required = vec![string("tag")];
+                        if let Some(payload) = payload {
+                            properties.push(("value", self.schema(payload, &[])));
+                            required.push(string("value"));
+                        }
+                        map(vec![
+                            ("type", string("object")),
+                            ("properties", map(properties)),
+                            ("required", Object::Array(required.into())),
+                            ("additionalProperties", Object::Bool(false)),
+                        ])
+                    }
+                },
+            )
+            .collect();
+
+        let constants: Option<Vec<Object>> = options
+            .iter()
+            .map(|option| match option {
+                Object::Map(keywords) if keywords.len() == 1 && &*keywords[0].0 == "const" => {
+                    Some(keywords[0].1.clone())
+                }
+                _ => None,
+            })
+            .collect();
+        match constants {
+            Some(constants) => map(vec![("enum", Object::Array(constants.into()))]),
+            // Untagged payloads can overlap, tags never do.
+            None if matches!(self.enum_encoding, EnumEncoding::Untagged) => {
+                map(vec![("anyOf", Object::Array(options.into()))])
+            }
+            None => map(vec![("oneOf", Object::Array(options.into()))]),
+        }
+    }
+
+    /// Adds keywords for the constraint, and warns when JSON Schema cannot
+    /// express part of it, the schema then accepts more than the constraint.
+    fn keywords(&self, ty: &Type, constraint: &Constraint, keywords: &mut Vec<(Rc<str>, Object)>) {
+        match constraint {
+            Constraint::Matching(_, _, pattern) => {
+                add(keywords, vec![("pattern".into(), string(pattern))]);
+            }
+            Constraint::Where(path, token, condition) => {
+                let mut complete = true;
+                for condition in conjuncts(condition) {
+                    match self.condition(ty, condition) {
+                        Some(schema) => add(keywords, schema),
+                        None => complete = false,
+                    }
+                }
+                if !complete {
+                    let path: Vec<&str> = path.iter().map(|p| &*p.0).collect();
+                    eprintln!(
+                        "hello.m:{}: Part of the constraint on '{}' cannot be expressed in JSON Schema, it is not exported",
+                        token.line,
+                        path.join(".")
+                    );
+                }
+            }
+            Constraint::Integer(_, _) => integer(keywords),
+            Constraint::Imported(_, constraint) => self.keywords(ty, constraint, keywords),
+        }
+    }
+
+    /// Keywords of a schema accepting the values the condition holds for,
+    /// `None` when JSON Schema cannot express it.
+    fn condition(&self, ty: &Type, condition: &Expr) -> Option<Vec<(Rc<str>, Object)>> {
+        match condition {
+            Expr::Binary(left, op, right) if op.kind == TokenType::AndAnd => {
+                let mut keywords = self.condition(ty, left)?;
+                add(&mut keywords, self.condition(ty, right)?);
+                Some(keywords)
+            }
+            Expr::Binary(left, op, right) if op.kind == TokenType::PipePipe => {
+                let left = Object::Map(self.condition(ty, left)?.into());
+                let right = Object::Map(self.condition(ty, right)?.into());
+                Some(vec![(
+                    "anyOf".into(),
+                    Object::Array(vec![left, right].into()),
+                )])
+            }
+            Expr::Unary(op, condition) if op.kind == TokenType::Bang => {
+                let negated = Object::Map(self.condition(ty, condition)?.into());
+                Some(vec![("not".into(), negated)])
+            }
+            Expr::Binary(left, op, right) => {
+                let (subject, op, value) = match (constant(left), constant(right)) {
+                    (None, Some(value)) => (&**left, op.kind, value),
+                    (Some(value), None) => (&**right, flip(op.kind), value),
+                    _ => return None,
+                };
+                if is_it(subject) {
+                    let keyword = match (op, &value) {
+                        (TokenType::EqualEqual, _) => "const",
+                        (TokenType::BangEqual, _) => {
+                            return Some(vec![("not".into(), map(vec![("const", value)]))]);
+                        }
+                        (_, Object::String(_)) => return None,
+                        (TokenType::GreaterEqual, _) => "minimum",
+                        (TokenType::RightCaret, _) => "exclusiveMinimum",
+                        (TokenType::LessEqual, _) => "maximum",
+                        (TokenType::LeftCaret, _) => "exclusiveMaximum",
+                        _ => return None,
+                    };
+                    Some(vec![(keyword.into(), value)])
+                } else if is_len_of_it(subject) {
+                    let n = match value {
+                        Object::Num(n) => n,
+                        _ => return None,
+                    };
+                    // Lengths are integers, so strict bounds move by one.
+                    let bounds = match op {
+                        TokenType::GreaterEqual => vec![("min", n)],
+                        TokenType::RightCaret => vec![("min", n.floor() + 1.0)],
+                        TokenType::LessEqual => vec![("max", n)],
+                        TokenType::LeftCaret => vec![("max", n.ceil() - 1.0)],
+                        TokenType::EqualEqual => vec![("min", n), ("max", n)],
+                        _ => return None,
+                    };
+                    let unit = match self.resolve(ty) {
+                        Type::Builtin(BuiltinType::String) => "Length",
+                        Type::Nested(base, _) if *base == Type::Builtin(BuiltinType::Map) => {
+                            "Properties"
+                        }
+                        _ => "Items",
+                    };
+                    Some(
+                        bounds
+                            .into_iter()
+                            .map(|(bound, n)| (format!("{}{}", bound, unit).into(), Object::Num(n)))
+                            .collect(),
+                    )
+                } else {
+                    None
+                }
+            }
+            Expr::Call(target, _, args) => match (&**target, &args[..]) {
+                (Expr::Variable(name), [subject, Expr::Literal(_, Object::String(pattern))])
+                    if name.lexeme == "matches" && is_it(subject) =>
+                {
+                    Some(vec![("pattern".into(), string(pattern))])
+                }
+                _ => None,
+            },
+            Expr::Grouping(condition) => self.condition(ty, condition),
+            _ => None,
+        }
+    }
+
+    /// Looks through nullables and aliases.
+    fn resolve(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Nullable(ty) => self.resolve(ty),
+            Type::Explicit(name) => match self.declaration(name) {
+                Some(Statement::Type(_, _, aliased)) => self.resolve(aliased),
+                _ => ty.clone(),
+            },
+            _ => ty.clone(),
+        }
+    }
+}
+
+fn is_it(expr: &Expr) -> bool {
+    matches!(expr, Expr::Variable(Token { lexeme: "it", .. }))
+}
+
+fn is_len_of_it(expr: &Expr) -> bool {
+    match expr {
+        Expr::Call(target, _, args) => {
+            matches!(&**target, Expr::Variable(Token { lexeme: "len", .. }))
+                && args.len() == 1
+                && is_it(&args[0])
+        }
+        _ => false,
+    }
+}
+
+/// `a && (b && c)` is `a`, `b` and `c`, so each part can be exported or not
+/// on its own.
+fn conjuncts<'e, 'a>(condition: &'e Expr<'a>) -> Vec<&'e Expr<'a>> {
+    match condition {
+        Expr::Binary(left, op, right) if op.kind == TokenType::AndAnd => {
+            let mut parts = conjuncts(left);
+            parts.extend(conjuncts(right));
+            parts
+        }
+        Expr::Grouping(condition) => conjuncts(condition),
+        condition => vec![condition],
+    }
+}
+
+/// Number and string literals, `-1` included.
+fn constant(expr: &Expr) -> Option<Object> {
+    match expr {
+        Expr::Literal(_, value @ (Object::Num(_) | Object::String(_))) => Some(value.clone()),
+        Expr::Unary(op, expr) if op.kind == TokenType::Minus => match constant(expr)? {
+            Object::Num(n) => Some(Object::Num(-n)),
+            _ => None,
+        },
+        Expr::Grouping(expr) => constant(expr),
+        _ => None,
+    }
+}
+
+/// Adds the keywords of a schema, keywords already present are kept and the
+/// schema joins `allOf` instead, so both apply.
+fn add(keywords: &mut Vec<(Rc<str>, Object)>, schema: Vec<(Rc<str>, Object)>) {
+    if !schema
+        .iter()
+        .any(|(key, _)| keywords.iter().any(|(k, _)| k == key))
+    {
+        keywords.extend(schema);
+        return;
+    }
+    let schema = Object::Map(schema.into());
+    match keywords.iter_mut().find(|(key, _)| &**key == "allOf") {
+        Some((_, Object::Array(schemas))) => Rc::make_mut(schemas).push(schema),
+        _ => keywords.push(("allOf".into(), Object::Array(vec![schema].into()))),
+    }
+}
+
+/// `1 <= it` is `it >= 1`.
+fn flip(op: TokenType) -> TokenType {
+    match op {
+        TokenType::LessEqual => TokenType::GreaterEqual,
+        TokenType::GreaterEqual => TokenType::LessEqual,
+        TokenType::LeftCaret => TokenType::RightCaret,
+        TokenType::RightCaret => TokenType::LeftCaret,
+        op => op,
+    }
+}
+
+fn map(entries: Vec<(&str, Object)>) -> Object {
+    let entries: Vec<(Rc<str>, Object)> = entries
+        .into_iter()
+        .map(|(key, value)| (key.into(), value))
+        .collect();
+    Object::Map(entries.into())
+}
+
+fn string(s: &str) -> Object {
+    Object::String(s.into())
+}
+
+fn kind(name: &str) -> Object {
+    map(vec![("type", string(name))])
+}
+
+/// `"number"` becomes `"integer"` in the type keyword, nullable or not.
+fn integer(keywords: &mut [(Rc<str>, Object)]) {
+    let number = string("number");
+    let narrow = |t: &Object| {
+        if *t == number {
+            string("integer")
+        } else {
+            t.clone()
+        }
+    };
+    for (key, value) in keywords.iter_mut() {
+        if &**key == "type" {
+            *value = match &*value {
+                Object::Array(types) => {
+                    Object::Array(types.iter().map(narrow).collect::<Vec<_>>().into())
+                }
+                t => narrow(t),
+            };
+        }
+    }
+}
+
+fn reference(name: &str) -> Object {
+    map(vec![("$ref", string(&format!("#/$defs/{}", name)))])
+}
+
+/// `{"type": "string"}` becomes `{"type": ["string", "null"]}`, other schemas
+/// are combined with `anyOf`.
+fn nullable(schema: Object) -> Object {
+    match schema {
+        Object::Map(keywords) if keywords.len() == 1 && &*keywords[0].0 == "type" => {
+            let ty = keywords[0].1.clone();
+            map(vec![(
+                "type",
+                Object::Array(vec![ty, string("null")].into()),
+            )])
+        }
+        schema => map(vec![(
+            "anyOf",
+            Object::Array(vec![schema, kind("null")].into()),
+        )]),
+    }
+}
@@ -4,6 +4,8 @@ pub struct Token<'a> {
     pub lexeme: &'a str,
     pub line: usize,
     pub col: usize,
+    // Always 0, nothing reads it yet.
+    #[allow(dead_code)]
     pub stack_offset: usize,
 }
 
@@ -21,25 +23,43 @@ pub enum TokenType {
     KIdentifier,
     Let,
     Ampersand,
+    AndAnd,
+    Bang,
+    BangEqual,
     Nil,
+    True,
+    False,
     Plus,
     Minus,
     Star,
     Slash,
+    SlashSlash,
+    SlashBackslash,
     If,
+    Else,
     For,
     Fn,
     Return,
     In,
     Question,
+    Pipe,
+    PipePipe,
     Dot,
+    DotDot,
     Semicolon,
     Colon,
     Comma,
     Struct,
+    Enum,
+    Match,
     Number,
     String,
     Equal,
+    EqualEqual,
+    LessEqual,
+    GreaterEqual,
+    FatArrow,
+    Arrow,
     Import,
     From,
     Pub,
@@ -0,0 +1,140 @@
+//! Debug events of the scanner, parser, type checker, compiler and VM.
+//!
+//! Events are written as a line each to stderr or to a file, keeping stdout
+//! for the output of the script. A text event reads
+//! `[vm] execute frame=main span=hello.m:1:12 op=Constant(0)`, the same event
+//! as JSON is `{"phase":"vm","event":"execute","frame":"main",...}`.
+use crate::formatter::quote;
+use std::cell::RefCell;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    Scanner,
+    Parser,
+    Checker,
+    Compiler,
+    Vm,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 5] = [
+        Phase::Scanner,
+        Phase::Parser,
+        Phase::Checker,
+        Phase::Compiler,
+        Phase::Vm,
+    ];
+
+    pub fn parse(name: &str) -> Option<Phase> {
+        Phase::ALL
+            .iter()
+            .copied()
+            .find(|phase| phase.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Scanner => "scanner",
+            Phase::Parser => "parser",
+            Phase::Checker => "checker",
+            Phase::Compiler => "compiler",
+            Phase::Vm => "vm",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceFormat {
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+/// Writes the events of the selected phases. Clones share the same output, the
+/// default traces nothing.
+#[derive(Clone)]
+pub struct Tracer {
+    phases: Vec<Phase>,
+    format: TraceFormat,
+    /// Stderr when None.
+    file: Option<Rc<RefCell<File>>>,
+}
+
+impl Default for Tracer {
+    fn default() -> Self {
+        Tracer::stderr(&[], TraceFormat::Text)
+    }
+}
+
+impl fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Tracer")
+            .field("phases", &self.phases)
+            .field("format", &self.format)
+            .finish()
+    }
+}
+
+impl Tracer {
+    pub fn stderr(phases: &[Phase], format: TraceFormat) -> Self {
+        Tracer {
+            phases: phases.to_vec(),
+            format,
+            file: None,
+        }
+    }
+
+    /// Writes to the file at `path`, truncating it.
+    pub fn file(path: impl AsRef<Path>, phases: &[Phase], format: TraceFormat) -> io::Result<Self> {
+        Ok(Tracer {
+            phases: phases.to_vec(),
+            format,
+            file: Some(Rc::new(RefCell::new(File::create(path)?))),
+        })
+    }
+
+    pub fn enabled(&self, phase: Phase) -> bool {
+        self.phases.contains(&phase)
+    }
+
+    /// Writes the event with the named fields when its phase is selected.
+    /// Fields are only built then, as some dump whole values.
+    pub fn event(
+        &self,
+        phase: Phase,
+        event: &str,
+        fields: impl FnOnce() -> Vec<(&'static str, String)>,
+    ) {
+        if !self.enabled(phase) {
+            return;
+        }
+        let fields = fields();
+        let mut line = match self.format {
+            TraceFormat::Text => format!("[{}] {}", phase.name(), event),
+            TraceFormat::Json => format!(
+                "{{\"phase\":\"{}\",\"event\":{}",
+                phase.name(),
+                quote(event)
+            ),
+        };
+        for (name, value) in &fields {
+            match self.format {
+                TraceFormat::Text => line.push_str(&format!(" {}={}", name, value)),
+                TraceFormat::Json => line.push_str(&format!(",{}:{}", quote(name), quote(value))),
+            }
+        }
+        if self.format == TraceFormat::Json {
+            line.push('}');
+        }
+        // Tracing is best effort, a failed write does not stop the script.
+        let _ = match &self.file {
+            Some(file) => writeln!(file.borrow_mut(), "{}", line),
+            None => writeln!(io::stderr(), "{}", line),
+        };
+    }
+}
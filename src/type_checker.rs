@@ -1,5 +1,11 @@
-use crate::parser::{BuiltinType, Expr, Field, FieldDeclaration, Object, Program, Statement, Type};
+use crate::parser::{
+    Arm, BuiltinType, Constraint, Expr, Field, FieldDeclaration, Kind, Object, Pattern, Program,
+    QualifiedName, Statement, Type, VariantDeclaration,
+};
+use crate::prelude;
+use crate::regex::Regex;
 use crate::token::{Token, TokenType};
+use crate::trace::{Phase, Tracer};
 
 #[derive(Debug)]
 struct Scope<'a> {
@@ -8,8 +14,10 @@ struct Scope<'a> {
 }
 
 impl<'a> Scope<'a> {
+    /// Later declarations shadow earlier ones, and the ones of enclosing
+    /// scopes.
     pub fn find(&self, token: &Token) -> Type {
-        for var in &self.variables {
+        for var in self.variables.iter().rev() {
             if var.0.lexeme == token.lexeme {
                 return var.1.clone();
             }
@@ -19,21 +27,50 @@ impl<'a> Scope<'a> {
         }
         panic!("Variable {:?} not found in {:?}", token, self);
     }
+
+    pub fn find_name(&self, name: &QualifiedName) -> Option<Type> {
+        for var in self.variables.iter().rev() {
+            if var.0.lexeme == name.0 {
+                return Some(var.1.clone());
+            }
+        }
+        self.enclosing.as_ref().and_then(|e| e.find_name(name))
+    }
 }
 
 pub struct TypeChecker<'a> {
     scope: Scope<'a>,
-    debug: bool,
+    /// Declared return type of the function being checked.
+    return_type: Option<Type>,
+    tracer: Tracer,
 }
 
 impl<'a> TypeChecker<'a> {
-    pub fn init(debug: bool) -> Self {
+    pub fn init(tracer: Tracer) -> Self {
         TypeChecker {
+            // Natives sit in a scope enclosing the program, which can shadow
+            // them.
             scope: Scope {
-                enclosing: None,
+                enclosing: Some(Box::new(Scope {
+                    enclosing: None,
+                    variables: prelude::NATIVES
+                        .iter()
+                        .map(|native| {
+                            let token = Token {
+                                kind: TokenType::Identifier,
+                                lexeme: native.name,
+                                line: 0,
+                                col: 0,
+                                stack_offset: 0,
+                            };
+                            (token, native.ty())
+                        })
+                        .collect(),
+                })),
                 variables: vec![],
             },
-            debug,
+            return_type: None,
+            tracer,
         }
     }
 
@@ -43,46 +80,358 @@ impl<'a> TypeChecker<'a> {
         }
     }
 
+    /// Type of a top level variable, once the program is checked.
+    pub fn variable(&self, name: &str) -> Option<Type> {
+        self.scope.find_name(&QualifiedName(name.to_string()))
+    }
+
     fn check_statement(&mut self, statement: &Statement<'a>) {
-        if self.debug {
-            println!("[Type Checker] Statement {:?}", statement);
-        }
+        self.tracer.event(Phase::Checker, "statement", || {
+            vec![("statement", format!("{:?}", statement))]
+        });
         match statement {
-            Statement::Return(_) => {}
-            Statement::Fn(_, name, _args, ret, _body) => self
-                .scope
-                .variables
-                .push((*name, Type::Fn(Box::new(ret.clone())))),
-            Statement::Variable(_, token, expr) => {
-                let ty = expr
+            Statement::Return(token, expr) => {
+                if self.return_type.is_none() {
+                    eprintln!(
+                        "hello.m:{}: Cannot return outside of a function",
+                        token.line
+                    );
+                    std::process::exit(324)
+                }
+                let expected = self.return_type.clone();
+                let ty = self.check_argument(expr, expected.as_ref());
+                if let Some(expected) = &self.return_type {
+                    if !self.accepts(expected, &ty) {
+                        eprintln!(
+                            "hello.m:{}: Expected '{}', got '{}'",
+                            token.line,
+                            expected.print(),
+                            ty.print()
+                        );
+                        std::process::exit(324)
+                    }
+                }
+            }
+            Statement::Fn(_, name, params, args, ret, body) => {
+                let ty = Type::Fn(
+                    args.iter().map(|a| a.1.clone()).collect(),
+                    Box::new(ret.clone()),
+                );
+                self.scope.variables.push((*name, generic(params, ty)));
+                self.check_function(name, args, ret, body);
+            }
+            Statement::Variable(_, token, declared, expr) => {
+                let mut ty = expr
                     .as_ref()
-                    .map(|e| self.check_expression(e))
+                    .map(|e| self.check_argument(e, declared.as_ref()))
                     .expect("Could not type check expression");
+                if let Some(declared) = declared {
+                    check_declared_type(token, declared);
+                    if !self.accepts(declared, &ty) {
+                        eprintln!(
+                            "hello.m:{}: Expected '{}', got '{}'",
+                            token.line,
+                            declared.print(),
+                            ty.print()
+                        );
+                        std::process::exit(324)
+                    }
+                    ty = declared.clone();
+                }
                 self.scope.variables.push((*token, ty));
             }
             Statement::Expr(expr) => {
                 self.check_expression(expr);
             }
-            Statement::Struct(_, name, fields) => {
+            Statement::Struct(_, name, params, fields, constraints) => {
+                for field in fields {
+                    check_declared_type(name, &field.1);
+                }
+                self.check_defaults(name, fields);
+                self.scope
+                    .variables
+                    .push((*name, generic(params, Type::Struct(fields.clone()))));
+                for constraint in constraints {
+                    self.check_constraint(fields, constraint);
+                }
+            }
+            Statement::Assert(token, condition, message) => {
+                self.expect(token, &Type::Builtin(BuiltinType::Bool), condition);
+                if let Some(message) = message {
+                    self.expect(token, &Type::Builtin(BuiltinType::String), message);
+                }
+            }
+            Statement::Enum(_, name, variants) => {
                 self.scope
                     .variables
-                    .push((*name, Type::Struct(fields.clone())));
+                    .push((*name, Type::Enum(variants.clone())));
+            }
+            Statement::Type(_, name, ty) => {
+                check_declared_type(name, ty);
+                self.scope.variables.push((*name, ty.clone()));
+            }
+            Statement::Import(name, module) => {
+                let module_name = &module.lexeme[1..module.lexeme.len() - 1];
+                // Schema modules are declared ahead of the script.
+                if module_name.ends_with(".json") {
+                    let name = QualifiedName(name.lexeme.to_string());
+                    if self.scope.find_name(&name).is_none() {
+                        eprintln!(
+                            "hello.m:{}: '{}' is not exported by '{}'",
+                            module.line, name.0, module_name
+                        );
+                        std::process::exit(324)
+                    }
+                    return;
+                }
+                if !prelude::is_module(module_name) {
+                    eprintln!("hello.m:{}: Unknown module '{}'", module.line, module_name);
+                    std::process::exit(324)
+                }
+                let exported = name.lexeme == module_name
+                    || prelude::find(name.lexeme).map(|n| n.module) == Some(module_name);
+                if !exported {
+                    eprintln!(
+                        "hello.m:{}: '{}' is not exported by '{}'",
+                        name.line, name.lexeme, module_name
+                    );
+                    std::process::exit(324)
+                }
             }
-            Statement::Import(_, _) => {}
         }
     }
 
-    fn check_expression(&mut self, expr: &Expr<'a>) -> Type {
-        if self.debug {
-            println!("[Type Checker] Expression {:?}", expr);
+    /// Checks the body in a scope holding the arguments, and that every path
+    /// through it returns.
+    fn check_function(
+        &mut self,
+        name: &Token,
+        args: &[(Token<'a>, Type)],
+        ret: &Type,
+        body: &[Statement<'a>],
+    ) {
+        self.begin_scope();
+        for (arg, ty) in args {
+            check_declared_type(arg, ty);
+            self.scope.variables.push((*arg, ty.clone()));
+        }
+        check_declared_type(name, ret);
+
+        let enclosing = self.return_type.replace(ret.clone());
+        for statement in body {
+            self.check_statement(statement);
+        }
+        self.return_type = enclosing;
+        self.end_scope();
+
+        if *ret != Type::Builtin(BuiltinType::Unit) && !returns(body) {
+            eprintln!(
+                "hello.m:{}: Function '{}' must return '{}'",
+                name.line,
+                name.lexeme,
+                ret.print()
+            );
+            std::process::exit(324)
         }
+    }
+
+    /// `where` conditions are checked with the field bound to `it`, nil values
+    /// are never checked.
+    fn check_constraint(&mut self, fields: &[FieldDeclaration], constraint: &Constraint<'a>) {
+        let token = constraint.token();
+        let mut ty = field_type(fields, constraint.path()).expect("Constrained field not found");
+        if let Type::Nullable(inner) = ty {
+            ty = *inner;
+        }
+        match constraint {
+            Constraint::Where(_, _, condition) => {
+                self.begin_scope();
+                let it = Token {
+                    kind: TokenType::Identifier,
+                    lexeme: "it",
+                    ..token
+                };
+                self.scope.variables.push((it, ty));
+                self.expect(&token, &Type::Builtin(BuiltinType::Bool), condition);
+                self.end_scope();
+            }
+            Constraint::Matching(_, _, pattern) => {
+                if self.resolve(&ty) != Type::Builtin(BuiltinType::String) {
+                    eprintln!(
+                        "hello.m:{}: Only 'String' fields can be matched, got '{}'",
+                        token.line,
+                        ty.print()
+                    );
+                    std::process::exit(324)
+                }
+                if let Err(e) = Regex::new(pattern) {
+                    eprintln!(
+                        "hello.m:{}: Invalid pattern '{}': {}",
+                        token.line, pattern, e
+                    );
+                    std::process::exit(324)
+                }
+            }
+            // Only added to fields declared as `Int`.
+            Constraint::Integer(_, _) => {}
+            Constraint::Imported(_, constraint) => self.check_constraint(fields, constraint),
+        }
+    }
+
+    fn expect(&mut self, token: &Token, expected: &Type, expr: &Expr<'a>) {
+        let ty = self.check_expression(expr);
+        if !self.accepts(expected, &ty) {
+            eprintln!(
+                "hello.m:{}: Expected '{}', got '{}'",
+                token.line,
+                expected.print(),
+                ty.print()
+            );
+            std::process::exit(324)
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        let enclosing = std::mem::replace(
+            &mut self.scope,
+            Scope {
+                enclosing: None,
+                variables: vec![],
+            },
+        );
+        self.scope.enclosing = Some(Box::new(enclosing));
+    }
+
+    fn end_scope(&mut self) {
+        let enclosing = self.scope.enclosing.take().expect("Unbalanced scopes");
+        self.scope = *enclosing;
+    }
+
+    fn check_expression(&mut self, expr: &Expr<'a>) -> Type {
+        self.tracer.event(Phase::Checker, "expression", || {
+            vec![("expression", format!("{:?}", expr))]
+        });
         let res = match expr {
-            Expr::Call(exp, _) => {
-                if let Type::Fn(ret) = self.check_expression(exp) {
-                    (&*ret).clone()
-                } else {
-                    panic!()
+            Expr::Call(exp, paren, args) => match self.check_expression(exp) {
+                Type::Fn(params, ret) => {
+                    check_arity(paren, &params, args);
+                    for (param, arg) in params.iter().zip(args) {
+                        let arg_ty = self.check_argument(arg, Some(param));
+                        if !self.accepts(param, &arg_ty) {
+                            eprintln!(
+                                "hello.m:{}: Expected '{}', got '{}'",
+                                paren.line,
+                                param.print(),
+                                arg_ty.print()
+                            );
+                            std::process::exit(324)
+                        }
+                    }
+                    *ret
+                }
+                Type::Generic(_, ty) => {
+                    if let Type::Fn(params, ret) = *ty {
+                        check_arity(paren, &params, args);
+                        let mut bindings = vec![];
+                        for (param, arg) in params.iter().zip(args) {
+                            let expected = substitute(param, &bindings);
+                            let arg_ty = self.check_argument(arg, Some(&expected));
+                            if !self.unify(param, &arg_ty, &mut bindings) {
+                                eprintln!(
+                                    "hello.m:{}: Expected '{}', got '{}'",
+                                    paren.line,
+                                    substitute(param, &bindings).print(),
+                                    arg_ty.print()
+                                );
+                                std::process::exit(324)
+                            }
+                        }
+                        substitute(&ret, &bindings)
+                    } else {
+                        panic!()
+                    }
+                }
+                ty => {
+                    eprintln!("hello.m:{}: Cannot call '{}'", paren.line, ty.print());
+                    std::process::exit(324)
+                }
+            },
+            Expr::Binary(left, op, right)
+                if op.kind == TokenType::SlashSlash || op.kind == TokenType::SlashBackslash =>
+            {
+                let left = self.check_expression(left);
+                let right = self.check_expression(right);
+
+                match (self.resolve(&left), self.resolve(&right)) {
+                    (Type::Struct(fields), Type::Struct(overrides)) => {
+                        Type::Struct(self.merge_types(op, fields, overrides))
+                    }
+                    (map, other) if is_map(&map) && is_map(&other) => {
+                        if self.accepts(&map, &other) {
+                            map
+                        } else if self.accepts(&other, &map) {
+                            other
+                        } else {
+                            eprintln!(
+                                "hello.m:{}: Cannot merge '{}' with '{}'",
+                                op.line,
+                                left.print(),
+                                right.print()
+                            );
+                            std::process::exit(324)
+                        }
+                    }
+                    _ => {
+                        eprintln!(
+                            "hello.m:{}: Cannot merge '{}' with '{}'",
+                            op.line,
+                            left.print(),
+                            right.print()
+                        );
+                        std::process::exit(324)
+                    }
+                }
+            }
+            Expr::Binary(left, op, right)
+                if op.kind == TokenType::AndAnd || op.kind == TokenType::PipePipe =>
+            {
+                let bool = Type::Builtin(BuiltinType::Bool);
+                self.expect(op, &bool, left);
+                self.expect(op, &bool, right);
+                bool
+            }
+            Expr::Binary(left, op, right) if is_comparison(op) => {
+                let left = self.check_expression(left);
+                let right = self.check_expression(right);
+
+                let comparable =
+                    if op.kind == TokenType::EqualEqual || op.kind == TokenType::BangEqual {
+                        self.accepts(&left, &right) || self.accepts(&right, &left)
+                    } else {
+                        let (l, r) = (self.resolve(&left), self.resolve(&right));
+                        l == r
+                            && (l == Type::Builtin(BuiltinType::Num)
+                                || l == Type::Builtin(BuiltinType::String))
+                    };
+                if !comparable {
+                    eprintln!(
+                        "hello.m:{}: Cannot compare '{}' with '{}'",
+                        op.line,
+                        left.print(),
+                        right.print()
+                    );
+                    std::process::exit(324)
                 }
+                Type::Builtin(BuiltinType::Bool)
+            }
+            Expr::Unary(op, value) => {
+                let expected = if op.kind == TokenType::Bang {
+                    Type::Builtin(BuiltinType::Bool)
+                } else {
+                    Type::Builtin(BuiltinType::Num)
+                };
+                self.expect(op, &expected, value);
+                expected
             }
             Expr::Binary(left, op, right) => {
                 let left = self.check_expression(left);
@@ -93,29 +442,173 @@ impl<'a> TypeChecker<'a> {
                 }
                 left
             }
-            Expr::Struct(token, fields) => {
-                if let Type::Struct(field_declarations) = self.scope.find(token) {
-                    for field_declaration in field_declarations {
-                        let i = find_in_fields(fields, &field_declaration);
-                        let field = &fields[i];
-                        let field_ty = self.check_expression(&field.1);
-                        if !field_declaration.1.can_be_inferred_from(&field_ty) {
+            Expr::Struct(token, base, fields) => {
+                let ty = match self.resolve(&self.scope.find(token)) {
+                    Type::Generic(params, ty) => {
+                        Type::Struct(self.instantiate(&params, *ty, base, fields))
+                    }
+                    ty => ty,
+                };
+                if let Type::Struct(field_declarations) = &ty {
+                    if let Some(base) = base {
+                        let base_ty = self.check_expression(base);
+                        if !self.accepts(&ty, &base_ty) {
                             eprintln!(
                                 "hello.m:{}: Expected '{}', got '{}'",
-                                field.0.line,
-                                field_declaration.1.print(),
-                                field_ty.print()
+                                token.line,
+                                token.lexeme,
+                                base_ty.print()
                             );
                             std::process::exit(324)
                         }
+                        self.check_overrides(token, field_declarations, fields);
+                    } else {
+                        self.check_fields(token, field_declarations, fields);
                     }
-                    self.scope.find(token)
+                    ty
                 } else {
                     panic!("Could not find structure declaration for {:?}", token);
                 }
             }
+            Expr::Anonymous(fields) => Type::Struct(
+                fields
+                    .iter()
+                    .map(|f| {
+                        FieldDeclaration::new(
+                            QualifiedName(f.0.lexeme.to_string()),
+                            self.check_expression(&f.1),
+                        )
+                    })
+                    .collect(),
+            ),
+            Expr::Variant(token, variant, payload) => {
+                let ty = self.scope.find(token);
+                if let Type::Enum(variants) = &ty {
+                    let declaration = find_variant(token.lexeme, variants, variant);
+                    match (&declaration.1, payload) {
+                        (Some(expected), Some(payload)) => {
+                            let payload_ty = self.check_expression(payload);
+                            if !self.accepts(expected, &payload_ty) {
+                                eprintln!(
+                                    "hello.m:{}: Expected '{}', got '{}'",
+                                    variant.line,
+                                    expected.print(),
+                                    payload_ty.print()
+                                );
+                                std::process::exit(324)
+                            }
+                        }
+                        (Some(expected), None) => {
+                            eprintln!(
+                                "hello.m:{}: Variant '{}' expects a '{}' payload",
+                                variant.line,
+                                variant.lexeme,
+                                expected.print()
+                            );
+                            std::process::exit(324)
+                        }
+                        (None, Some(_)) => {
+                            eprintln!(
+                                "hello.m:{}: Variant '{}' takes no payload",
+                                variant.line, variant.lexeme
+                            );
+                            std::process::exit(324)
+                        }
+                        (None, None) => {}
+                    }
+                    ty
+                } else {
+                    panic!("Could not find enum declaration for {:?}", token);
+                }
+            }
+            Expr::Match(token, value, arms) => self.check_match(token, value, arms),
+            Expr::Lambda(params, body) => self.check_lambda(params, body, None),
+            Expr::Map(brace, entries) => {
+                let mut ty = Type::Infer;
+                for (key, value) in entries {
+                    let key_ty = self.check_expression(key);
+                    if key_ty != Type::Builtin(BuiltinType::String) {
+                        eprintln!(
+                            "hello.m:{}: Map keys must be 'String', got '{}'",
+                            brace.line,
+                            key_ty.print()
+                        );
+                        std::process::exit(324)
+                    }
+                    let value_ty = self.check_expression(value);
+                    if value_ty == Type::Infer {
+                        continue;
+                    } else if ty == Type::Infer {
+                        ty = value_ty;
+                    } else if ty != value_ty {
+                        eprintln!(
+                            "hello.m:{}: Literal map can only have a single value type",
+                            brace.line
+                        );
+                        std::process::exit(324)
+                    }
+                }
+                map_type(ty)
+            }
+            Expr::Index(value, bracket, index) => {
+                let value_ty = self.check_expression(value);
+                let index_ty = self.check_expression(index);
+                let item_ty = match (self.resolve(&value_ty), &index_ty) {
+                    (Type::Nested(base, mut args), Type::Builtin(BuiltinType::Num))
+                        if *base == Type::Builtin(BuiltinType::Array) =>
+                    {
+                        args.pop()
+                    }
+                    (Type::Nested(base, mut args), Type::Builtin(BuiltinType::String))
+                        if *base == Type::Builtin(BuiltinType::Map) =>
+                    {
+                        args.pop()
+                    }
+                    _ => None,
+                };
+                match item_ty {
+                    Some(Type::Nullable(ty)) => Type::Nullable(ty),
+                    Some(ty) => Type::Nullable(Box::new(ty)),
+                    None => {
+                        eprintln!(
+                            "hello.m:{}: Cannot index '{}' with '{}'",
+                            bracket.line,
+                            value_ty.print(),
+                            index_ty.print()
+                        );
+                        std::process::exit(324)
+                    }
+                }
+            }
+            Expr::For(token, key, value, iterable, body) => {
+                let iterable_ty = self.check_expression(iterable);
+                let (key_ty, value_ty) = match self.resolve(&iterable_ty) {
+                    Type::Nested(base, mut args) if *base == Type::Builtin(BuiltinType::Array) => {
+                        (Type::Builtin(BuiltinType::Num), args.pop().unwrap())
+                    }
+                    Type::Nested(base, mut args) if *base == Type::Builtin(BuiltinType::Map) => {
+                        (Type::Builtin(BuiltinType::String), args.pop().unwrap())
+                    }
+                    _ => {
+                        eprintln!(
+                            "hello.m:{}: Cannot iterate over '{}'",
+                            token.line,
+                            iterable_ty.print()
+                        );
+                        std::process::exit(324)
+                    }
+                };
+                self.begin_scope();
+                if let Some(key) = key {
+                    self.scope.variables.push((*key, key_ty));
+                }
+                self.scope.variables.push((*value, value_ty));
+                let ty = self.check_expression(body);
+                self.end_scope();
+                Type::Nested(Box::new(Type::Builtin(BuiltinType::Array)), vec![ty])
+            }
             Expr::Grouping(expr) => self.check_expression(expr),
-            Expr::Array(exprs) => {
+            Expr::Array(_, exprs) => {
                 let mut ty = Type::Infer;
                 for expr in exprs {
                     let item_ty = self.check_expression(expr);
@@ -128,39 +621,791 @@ impl<'a> TypeChecker<'a> {
                         std::process::exit(231)
                     }
                 }
-                Type::Nested(Box::new(Type::Builtin(BuiltinType::Array)), Box::new(ty))
+                Type::Nested(Box::new(Type::Builtin(BuiltinType::Array)), vec![ty])
             }
-            Expr::Literal(object) => object_type(object),
+            Expr::Literal(_, object) => object_type(object),
             Expr::Variable(token) => self.scope.find(token),
         };
-        if self.debug {
-            println!("[Type Checker] type of expression: {:?}", res);
-        }
+        self.tracer.event(Phase::Checker, "type", || {
+            vec![("type", format!("{:?}", res))]
+        });
         res
     }
+
+    /// Checks an expression whose type is known in advance, which lambdas use
+    /// to infer the type of their parameters.
+    fn check_argument(&mut self, expr: &Expr<'a>, expected: Option<&Type>) -> Type {
+        if let Expr::Lambda(params, body) = expr {
+            self.check_lambda(params, body, expected)
+        } else {
+            self.check_expression(expr)
+        }
+    }
+
+    fn check_lambda(
+        &mut self,
+        params: &[(Token<'a>, Option<Type>)],
+        body: &Expr<'a>,
+        expected: Option<&Type>,
+    ) -> Type {
+        let expected_args = match expected.map(|e| self.resolve(e)) {
+            Some(Type::Fn(args, _)) if args.len() == params.len() => args,
+            _ => vec![],
+        };
+
+        self.begin_scope();
+        let mut args = vec![];
+        for (i, (param, ty)) in params.iter().enumerate() {
+            let ty = match (ty, expected_args.get(i)) {
+                (Some(ty), _) => ty.clone(),
+                (None, Some(expected)) => expected.clone(),
+                (None, None) => {
+                    eprintln!(
+                        "hello.m:{}: Cannot infer the type of '{}'",
+                        param.line, param.lexeme
+                    );
+                    std::process::exit(324)
+                }
+            };
+            check_declared_type(param, &ty);
+            self.scope.variables.push((*param, ty.clone()));
+            args.push(ty);
+        }
+        let enclosing = self.return_type.take();
+        let ret = self.check_expression(body);
+        self.return_type = enclosing;
+        self.end_scope();
+
+        Type::Fn(args, Box::new(ret))
+    }
+
+    fn check_match(&mut self, token: &Token, value: &Expr<'a>, arms: &[Arm<'a>]) -> Type {
+        let value_ty = self.check_expression(value);
+        let resolved = self.resolve(&value_ty);
+        if !matches!(resolved, Type::Enum(_) | Type::Union(_)) {
+            eprintln!(
+                "hello.m:{}: Cannot match on '{}'",
+                token.line,
+                value_ty.print()
+            );
+            std::process::exit(324)
+        }
+
+        let mut covered = vec![];
+        let mut wildcard = false;
+        let mut ty = Type::Infer;
+        for (i, Arm(pattern, expr)) in arms.iter().enumerate() {
+            self.begin_scope();
+            match (pattern, &resolved) {
+                (Pattern::Variant(name, binding), Type::Enum(variants)) => {
+                    let declaration = find_variant(&value_ty.print(), variants, name);
+                    match (binding, &declaration.1) {
+                        (Some(binding), Some(payload_ty)) => {
+                            self.scope.variables.push((*binding, payload_ty.clone()))
+                        }
+                        (Some(_), None) => {
+                            eprintln!(
+                                "hello.m:{}: Variant '{}' has no payload to bind",
+                                name.line, name.lexeme
+                            );
+                            std::process::exit(324)
+                        }
+                        (None, _) => {}
+                    }
+                    covered.push(name.lexeme.to_string());
+                }
+                (Pattern::Type(binding, pattern_ty), Type::Union(options)) => {
+                    covered.extend(self.narrow(binding, &value_ty, options, pattern_ty));
+                    if binding.lexeme != "_" {
+                        self.scope.variables.push((*binding, pattern_ty.clone()));
+                    }
+                }
+                (Pattern::Wildcard(underscore), _) => {
+                    if i != arms.len() - 1 {
+                        eprintln!(
+                            "hello.m:{}: '_' must be the last match arm",
+                            underscore.line
+                        );
+                        std::process::exit(324)
+                    }
+                    wildcard = true
+                }
+                (Pattern::Variant(name, _), _) => {
+                    eprintln!(
+                        "hello.m:{}: Expected a type pattern on '{}', got '{}'",
+                        name.line,
+                        value_ty.print(),
+                        name.lexeme
+                    );
+                    std::process::exit(324)
+                }
+                (Pattern::Type(binding, pattern_ty), _) => {
+                    eprintln!(
+                        "hello.m:{}: Expected a variant of '{}', got '{}'",
+                        binding.line,
+                        value_ty.print(),
+                        pattern_ty.print()
+                    );
+                    std::process::exit(324)
+                }
+            }
+            let arm_ty = self.check_expression(expr);
+            self.end_scope();
+            ty = self.join_arms(token, ty, arm_ty);
+        }
+
+        let members: Vec<String> = match resolved {
+            Type::Enum(variants) => variants.into_iter().map(|v| v.0 .0).collect(),
+            Type::Union(options) => options.iter().map(|o| o.print()).collect(),
+            _ => unreachable!(),
+        };
+        let missing: Vec<String> = members
+            .iter()
+            .filter(|m| !covered.contains(m))
+            .map(|m| format!("'{}'", m))
+            .collect();
+        if !wildcard && !missing.is_empty() {
+            eprintln!(
+                "hello.m:{}: Non-exhaustive match, missing {}",
+                token.line,
+                missing.join(", ")
+            );
+            std::process::exit(324)
+        }
+        ty
+    }
+
+    /// Members of a union covered by a `binding: Type` pattern. The pattern is
+    /// tested on the runtime representation, so it must not also accept a
+    /// member it does not cover.
+    fn narrow(
+        &self,
+        binding: &Token,
+        union: &Type,
+        options: &[Type],
+        pattern_ty: &Type,
+    ) -> Vec<String> {
+        let covered: Vec<String> = options
+            .iter()
+            .filter(|o| self.accepts(pattern_ty, o))
+            .map(|o| o.print())
+            .collect();
+        if covered.is_empty() {
+            eprintln!(
+                "hello.m:{}: '{}' is not a member of '{}'",
+                binding.line,
+                pattern_ty.print(),
+                union.print()
+            );
+            std::process::exit(324)
+        }
+
+        let kind = self.kind(pattern_ty);
+        if kind.is_none() {
+            eprintln!(
+                "hello.m:{}: Cannot match on '{}' at runtime",
+                binding.line,
+                pattern_ty.print()
+            );
+            std::process::exit(324)
+        }
+        if let Some(other) = options
+            .iter()
+            .find(|o| !self.accepts(pattern_ty, o) && self.kind(o) == kind)
+        {
+            eprintln!(
+                "hello.m:{}: Cannot tell '{}' from '{}' at runtime",
+                binding.line,
+                pattern_ty.print(),
+                other.print()
+            );
+            std::process::exit(324)
+        }
+        covered
+    }
+
+    /// Runtime representation of the values of a type, see `Object::kind`.
+    fn kind(&self, ty: &Type) -> Option<Kind> {
+        self.resolve(ty)
+            .kind(&|name| match self.resolve(&Type::Explicit(name.clone())) {
+                Type::Explicit(_) => None,
+                ty => self.kind(&ty),
+            })
+    }
+
+    /// Type of a match whose arms so far have type `ty`, after adding an arm of
+    /// type `arm_ty`. A `nil` arm makes the whole match nullable.
+    fn join_arms(&self, token: &Token, ty: Type, arm_ty: Type) -> Type {
+        let nil = Type::Nullable(Box::new(Type::Infer));
+        if ty == Type::Infer {
+            arm_ty
+        } else if ty == nil {
+            if let Type::Nullable(_) = arm_ty {
+                arm_ty
+            } else {
+                Type::Nullable(Box::new(arm_ty))
+            }
+        } else if arm_ty == nil {
+            if let Type::Nullable(_) = ty {
+                ty
+            } else {
+                Type::Nullable(Box::new(ty))
+            }
+        } else if self.accepts(&ty, &arm_ty) {
+            ty
+        } else if self.accepts(&arm_ty, &ty) {
+            arm_ty
+        } else {
+            eprintln!(
+                "hello.m:{}: Match arms have different types '{}' and '{}'",
+                token.line,
+                ty.print(),
+                arm_ty.print()
+            );
+            std::process::exit(324)
+        }
+    }
+
+    fn check_fields(
+        &mut self,
+        token: &Token,
+        field_declarations: &[FieldDeclaration],
+        fields: &[Field<'a>],
+    ) {
+        check_known_fields(token, field_declarations, fields);
+        for field_declaration in field_declarations {
+            if let Some(field) = fields.iter().find(|f| f.0.lexeme == field_declaration.0 .0) {
+                self.check_field(field_declaration, field);
+            } else if !self.is_optional(field_declaration) {
+                eprintln!(
+                    "hello.m:{}: Missing field '{}' in '{}'",
+                    token.line, field_declaration.0 .0, token.lexeme
+                );
+                std::process::exit(324)
+            }
+        }
+    }
+
+    /// A field can be omitted when it has a default, is nullable, or is a
+    /// struct whose fields can all be omitted.
+    fn is_optional(&self, field_declaration: &FieldDeclaration) -> bool {
+        field_declaration.2.is_some()
+            || match self.resolve(&field_declaration.1) {
+                Type::Nullable(_) => true,
+                Type::Struct(nested) => nested.iter().all(|d| self.is_optional(d)),
+                _ => false,
+            }
+    }
+
+    fn check_defaults(&self, token: &Token, field_declarations: &[FieldDeclaration]) {
+        for field_declaration in field_declarations {
+            if let Some(Object::Variant(tag, None)) = &field_declaration.2 {
+                let mut ty = self.resolve(&field_declaration.1);
+                if let Type::Nullable(inner) = ty {
+                    ty = self.resolve(&inner);
+                }
+                let known = match ty {
+                    Type::Enum(variants) => {
+                        variants.iter().any(|v| *v.0 .0 == **tag && v.1.is_none())
+                    }
+                    _ => false,
+                };
+                if !known {
+                    eprintln!(
+                        "hello.m:{}: Expected '{}', got '{}'",
+                        token.line,
+                        field_declaration.1.print(),
+                        tag
+                    );
+                    std::process::exit(324)
+                }
+            } else if let Some(default) = &field_declaration.2 {
+                let default_ty = object_type(default);
+                if !self.accepts(&field_declaration.1, &default_ty) {
+                    eprintln!(
+                        "hello.m:{}: Expected '{}', got '{}'",
+                        token.line,
+                        field_declaration.1.print(),
+                        default_ty.print()
+                    );
+                    std::process::exit(324)
+                }
+            }
+            if let Type::Struct(nested) = &field_declaration.1 {
+                self.check_defaults(token, nested);
+            }
+        }
+    }
+
+    /// Fields of a `..base` literal are optional, the base provides the others,
+    /// and so are the ones of the nested structs its dotted paths update.
+    fn check_overrides(
+        &mut self,
+        token: &Token,
+        field_declarations: &[FieldDeclaration],
+        fields: &[Field<'a>],
+    ) {
+        check_known_fields(token, field_declarations, fields);
+        for field in fields {
+            if let Some(field_declaration) =
+                field_declarations.iter().find(|d| d.0 .0 == field.0.lexeme)
+            {
+                match (self.resolve(&field_declaration.1), &field.1) {
+                    (Type::Struct(nested), Expr::Anonymous(nested_fields)) => {
+                        self.check_overrides(&field.0, &nested, nested_fields)
+                    }
+                    _ => self.check_field(field_declaration, field),
+                }
+            }
+        }
+    }
+
+    fn check_field(&mut self, field_declaration: &FieldDeclaration, field: &Field<'a>) {
+        if let (Type::Struct(nested), Expr::Anonymous(nested_fields)) =
+            (self.resolve(&field_declaration.1), &field.1)
+        {
+            self.check_fields(&field.0, &nested, nested_fields);
+            return;
+        }
+
+        let field_ty = self.check_expression(&field.1);
+        if !self.accepts(&field_declaration.1, &field_ty) {
+            eprintln!(
+                "hello.m:{}: Expected '{}', got '{}'",
+                field.0.line,
+                field_declaration.1.print(),
+                field_ty.print()
+            );
+            std::process::exit(324)
+        }
+    }
+
+    /// Computes the record type of `//` (right-biased) and `/\` (recursive)
+    /// merges. A deep merge rejects fields whose types do not agree.
+    fn merge_types(
+        &self,
+        op: &Token,
+        mut fields: Vec<FieldDeclaration>,
+        overrides: Vec<FieldDeclaration>,
+    ) -> Vec<FieldDeclaration> {
+        for o in overrides {
+            if let Some(field) = fields.iter_mut().find(|f| f.0 == o.0) {
+                if op.kind == TokenType::SlashSlash {
+                    field.1 = o.1;
+                } else if let (Type::Struct(nested), Type::Struct(nested_overrides)) =
+                    (self.resolve(&field.1), self.resolve(&o.1))
+                {
+                    field.1 = Type::Struct(self.merge_types(op, nested, nested_overrides));
+                } else if !self.accepts(&field.1, &o.1) {
+                    eprintln!(
+                        "hello.m:{}: Conflicting types for field '{}': '{}' and '{}'",
+                        op.line,
+                        o.0 .0,
+                        field.1.print(),
+                        o.1.print()
+                    );
+                    std::process::exit(324)
+                }
+            } else {
+                fields.push(o);
+            }
+        }
+        fields
+    }
+
+    /// Infers the type parameters of a generic struct from a literal, and
+    /// returns its field declarations with the parameters replaced.
+    fn instantiate(
+        &mut self,
+        params: &[QualifiedName],
+        ty: Type,
+        base: &Option<Box<Expr<'a>>>,
+        fields: &[Field<'a>],
+    ) -> Vec<FieldDeclaration> {
+        let mut bindings = vec![];
+        if let Some(base) = base {
+            let base_ty = self.check_expression(base);
+            self.unify(&ty, &base_ty, &mut bindings);
+        }
+        if let Type::Struct(field_declarations) = &ty {
+            for field in fields {
+                if let Some(declaration) =
+                    field_declarations.iter().find(|d| d.0 .0 == field.0.lexeme)
+                {
+                    let field_ty = self.check_expression(&field.1);
+                    self.unify(&declaration.1, &field_ty, &mut bindings);
+                }
+            }
+        }
+        for param in params {
+            if !bindings.iter().any(|b| &b.0 == param) {
+                bindings.push((param.clone(), Type::Infer));
+            }
+        }
+
+        if let Type::Struct(field_declarations) = substitute(&ty, &bindings) {
+            field_declarations
+        } else {
+            panic!("Generic declaration {:?} is not a struct", ty);
+        }
+    }
+
+    /// Matches the declared type against the actual one, binding the type
+    /// parameters found on the way. Returns false on mismatch.
+    fn unify(
+        &self,
+        expected: &Type,
+        actual: &Type,
+        bindings: &mut Vec<(QualifiedName, Type)>,
+    ) -> bool {
+        match (self.resolve(expected), self.resolve(actual)) {
+            (_, Type::Infer) => true,
+            (Type::Param(name), actual) => {
+                if let Some(binding) = bindings.iter_mut().find(|b| b.0 == name) {
+                    if binding.1 == Type::Infer || self.accepts(&actual, &binding.1) {
+                        binding.1 = actual;
+                        true
+                    } else {
+                        self.accepts(&binding.1, &actual)
+                    }
+                } else {
+                    bindings.push((name, actual));
+                    true
+                }
+            }
+            (Type::Nullable(t), Type::Nullable(other)) => self.unify(&t, &other, bindings),
+            (Type::Nullable(t), other) => self.unify(&t, &other, bindings),
+            (Type::Nested(base, n), Type::Nested(other_base, other_n)) => {
+                self.unify(&base, &other_base, bindings)
+                    && n.len() == other_n.len()
+                    && n.iter()
+                        .zip(&other_n)
+                        .all(|(n, other_n)| self.unify(n, other_n, bindings))
+            }
+            (Type::Fn(args, ret), Type::Fn(other_args, other_ret)) => {
+                args.len() == other_args.len()
+                    && args
+                        .iter()
+                        .zip(&other_args)
+                        .all(|(a, other_a)| self.unify(a, other_a, bindings))
+                    && self.unify(&ret, &other_ret, bindings)
+            }
+            (Type::Union(options), actual) if !matches!(actual, Type::Union(_)) => {
+                options.iter().any(|o| {
+                    let mut attempt = bindings.clone();
+                    let unified = self.unify(o, &actual, &mut attempt);
+                    if unified {
+                        *bindings = attempt;
+                    }
+                    unified
+                })
+            }
+            (Type::Struct(declarations), Type::Struct(fields)) => declarations.iter().all(|d| {
+                fields
+                    .iter()
+                    .find(|f| f.0 == d.0)
+                    .map(|f| self.unify(&d.1, &f.1, bindings))
+                    .unwrap_or(true)
+            }),
+            (expected, actual) => self.accepts(&expected, &actual),
+        }
+    }
+
+    /// Replaces a struct or alias name by its declaration, leaving other types
+    /// untouched.
+    /// Only the outer level is resolved, so recursive declarations stay finite.
+    fn resolve(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Explicit(name) => match self.scope.find_name(name) {
+                // Aliases of aliases.
+                Some(alias @ Type::Explicit(_)) if &alias != ty => self.resolve(&alias),
+                Some(alias @ Type::Nested(_, _)) => self.resolve(&alias),
+                Some(found) => found,
+                None => ty.clone(),
+            },
+            Type::Nested(base, args) => {
+                if let Type::Explicit(name) = &**base {
+                    if let Some(Type::Generic(params, ty)) = self.scope.find_name(name) {
+                        let bindings: Vec<(QualifiedName, Type)> =
+                            params.into_iter().zip(args.iter().cloned()).collect();
+                        return substitute(&ty, &bindings);
+                    }
+                }
+                ty.clone()
+            }
+            _ => ty.clone(),
+        }
+    }
+
+    /// Structural version of `Type::can_be_inferred_from` that looks through
+    /// struct names and validates nested structs field by field.
+    fn accepts(&self, expected: &Type, actual: &Type) -> bool {
+        match (self.resolve(expected), self.resolve(actual)) {
+            (Type::Struct(declarations), Type::Struct(fields)) => {
+                declarations.len() == fields.len()
+                    && declarations.iter().all(|d| {
+                        fields
+                            .iter()
+                            .find(|f| f.0 == d.0)
+                            .map(|f| self.accepts(&d.1, &f.1))
+                            .unwrap_or(false)
+                    })
+            }
+            (Type::Union(options), Type::Union(others)) => others
+                .iter()
+                .all(|other| options.iter().any(|o| self.accepts(o, other))),
+            (Type::Union(options), actual) => options.iter().any(|o| self.accepts(o, &actual)),
+            (expected, Type::Union(others)) => others.iter().all(|o| self.accepts(&expected, o)),
+            (Type::Fn(args, ret), Type::Fn(other_args, other_ret)) => {
+                args.len() == other_args.len()
+                    && args
+                        .iter()
+                        .zip(&other_args)
+                        .all(|(a, other_a)| self.accepts(other_a, a))
+                    && self.accepts(&ret, &other_ret)
+            }
+            (Type::Nullable(t), Type::Nullable(other)) => self.accepts(&t, &other),
+            (Type::Nullable(t), other) => self.accepts(&t, &other),
+            (Type::Nested(base, n), Type::Nested(other_base, other_n)) => {
+                self.accepts(&base, &other_base)
+                    && n.len() == other_n.len()
+                    && n.iter()
+                        .zip(&other_n)
+                        .all(|(n, other_n)| self.accepts(n, other_n))
+            }
+            (expected, actual) => expected.can_be_inferred_from(&actual),
+        }
+    }
+}
+
+fn generic(params: &[QualifiedName], ty: Type) -> Type {
+    if params.is_empty() {
+        ty
+    } else {
+        Type::Generic(params.to_vec(), Box::new(ty))
+    }
+}
+
+/// Replaces the bound type parameters of a type.
+fn substitute(ty: &Type, bindings: &[(QualifiedName, Type)]) -> Type {
+    match ty {
+        Type::Param(name) => bindings
+            .iter()
+            .find(|b| &b.0 == name)
+            .map(|b| b.1.clone())
+            .unwrap_or_else(|| ty.clone()),
+        Type::Nullable(ty) => Type::Nullable(Box::new(substitute(ty, bindings))),
+        Type::Nested(base, nested) => Type::Nested(
+            Box::new(substitute(base, bindings)),
+            nested.iter().map(|n| substitute(n, bindings)).collect(),
+        ),
+        Type::Struct(fields) => Type::Struct(
+            fields
+                .iter()
+                .map(|f| FieldDeclaration(f.0.clone(), substitute(&f.1, bindings), f.2.clone()))
+                .collect(),
+        ),
+        Type::Fn(args, ret) => Type::Fn(
+            args.iter().map(|a| substitute(a, bindings)).collect(),
+            Box::new(substitute(ret, bindings)),
+        ),
+        Type::Union(options) => {
+            Type::Union(options.iter().map(|o| substitute(o, bindings)).collect())
+        }
+        ty => ty.clone(),
+    }
+}
+
+/// Whether every path through the statements reaches a `return`. They run in
+/// sequence and only expressions branch, so a path returns once one of the
+/// statements does.
+fn returns(statements: &[Statement]) -> bool {
+    statements.iter().any(|statement| match statement {
+        Statement::Return(_, _) => true,
+        // The returns of a nested function leave that function.
+        Statement::Fn(_, _, _, _, _, _) => false,
+        Statement::Variable(_, _, _, _)
+        | Statement::Expr(_)
+        | Statement::Struct(_, _, _, _, _)
+        | Statement::Enum(_, _, _)
+        | Statement::Type(_, _, _)
+        | Statement::Import(_, _)
+        | Statement::Assert(_, _, _) => false,
+    })
+}
+
+fn check_arity(paren: &Token, params: &[Type], args: &[Expr]) {
+    if params.len() != args.len() {
+        eprintln!(
+            "hello.m:{}: Expected {} arguments, got {}",
+            paren.line,
+            params.len(),
+            args.len()
+        );
+        std::process::exit(324)
+    }
 }
 
-fn find_in_fields(fields: &[Field], declaration: &FieldDeclaration) -> usize {
+/// Rejects literal fields that are not declared, or that are given twice.
+fn check_known_fields(token: &Token, field_declarations: &[FieldDeclaration], fields: &[Field]) {
     for (i, field) in fields.iter().enumerate() {
-        if declaration.0 .0 == field.0.lexeme {
-            return i;
+        if fields[..i].iter().any(|f| f.0.lexeme == field.0.lexeme) {
+            eprintln!(
+                "hello.m:{}: Field '{}' is given twice in '{}'",
+                field.0.line, field.0.lexeme, token.lexeme
+            );
+            std::process::exit(324)
+        }
+
+        if !field_declarations.iter().any(|d| d.0 .0 == field.0.lexeme) {
+            let suggestion = suggest(field.0.lexeme, field_declarations.iter().map(|d| &*d.0 .0));
+            if let Some(name) = suggestion {
+                eprintln!(
+                    "hello.m:{}: Unknown field '{}' in '{}', did you mean '{}'?",
+                    field.0.line, field.0.lexeme, token.lexeme, name
+                );
+            } else {
+                eprintln!(
+                    "hello.m:{}: Unknown field '{}' in '{}'",
+                    field.0.line, field.0.lexeme, token.lexeme
+                );
+            }
+            std::process::exit(324)
+        }
+    }
+}
+
+fn find_variant<'v>(
+    owner: &str,
+    variants: &'v [VariantDeclaration],
+    variant: &Token,
+) -> &'v VariantDeclaration {
+    if let Some(declaration) = variants.iter().find(|v| v.0 .0 == variant.lexeme) {
+        return declaration;
+    }
+
+    if let Some(name) = suggest(variant.lexeme, variants.iter().map(|v| &*v.0 .0)) {
+        eprintln!(
+            "hello.m:{}: Unknown variant '{}' in '{}', did you mean '{}'?",
+            variant.line, variant.lexeme, owner, name
+        );
+    } else {
+        eprintln!(
+            "hello.m:{}: Unknown variant '{}' in '{}'",
+            variant.line, variant.lexeme, owner
+        );
+    }
+    std::process::exit(324)
+}
+
+/// Closest candidate to a misspelled name, if any is close enough.
+fn suggest<'n>(name: &str, candidates: impl Iterator<Item = &'n str>) -> Option<&'n str> {
+    candidates
+        .map(|candidate| (edit_distance(candidate, name), candidate))
+        .filter(|(distance, candidate)| *distance <= (candidate.len() / 3).max(1))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance, used to suggest the closest field name.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == *cb { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
         }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+fn map_type(value: Type) -> Type {
+    Type::Nested(
+        Box::new(Type::Builtin(BuiltinType::Map)),
+        vec![Type::Builtin(BuiltinType::String), value],
+    )
+}
+
+fn is_map(ty: &Type) -> bool {
+    matches!(ty, Type::Nested(base, _) if **base == Type::Builtin(BuiltinType::Map))
+}
+
+/// Maps are only keyed by strings, as they are written out as objects.
+fn check_declared_type(token: &Token, ty: &Type) {
+    match ty {
+        Type::Nested(base, args) => {
+            if **base == Type::Builtin(BuiltinType::Map)
+                && (args.len() != 2 || args[0] != Type::Builtin(BuiltinType::String))
+            {
+                eprintln!(
+                    "hello.m:{}: Expected 'Map<String, T>', got '{}'",
+                    token.line,
+                    ty.print()
+                );
+                std::process::exit(324)
+            }
+            args.iter().for_each(|arg| check_declared_type(token, arg));
+        }
+        Type::Nullable(ty) => check_declared_type(token, ty),
+        Type::Union(options) => options.iter().for_each(|o| check_declared_type(token, o)),
+        Type::Fn(args, ret) => {
+            args.iter().for_each(|a| check_declared_type(token, a));
+            check_declared_type(token, ret);
+        }
+        Type::Struct(fields) => fields.iter().for_each(|f| check_declared_type(token, &f.1)),
+        _ => {}
     }
-    panic!("declaration {:?} not found...", declaration);
 }
 
 fn object_type(object: &Object) -> Type {
     match object {
         Object::Num(_) => Type::Builtin(BuiltinType::Num),
         Object::String(_) => Type::Builtin(BuiltinType::String),
+        Object::Bool(_) => Type::Builtin(BuiltinType::Bool),
         Object::Struct(_) => {
             panic!("Struct should not be instantiated in the type checker")
         }
-        Object::Array(_) => {
-            panic!("Struct should not be instantiated in the type checker")
-        }
+        Object::Array(items) => Type::Nested(
+            Box::new(Type::Builtin(BuiltinType::Array)),
+            vec![items.first().map(object_type).unwrap_or(Type::Infer)],
+        ),
+        Object::Map(entries) => map_type(
+            entries
+                .first()
+                .map(|e| object_type(&e.1))
+                .unwrap_or(Type::Infer),
+        ),
         Object::Nil => Type::Nullable(Box::new(Type::Infer)),
-        Object::Function(_, _, _, ty) => Type::Fn(Box::new(ty.clone())),
+        // The enum cannot be known from the variant alone.
+        Object::Variant(_, _) => Type::Infer,
+        Object::Function(prototype, _) => prototype.ty.clone(),
+        Object::NativeFunction(native) => native.ty(),
+    }
+}
+
+fn is_comparison(op: &Token) -> bool {
+    matches!(
+        op.kind,
+        TokenType::EqualEqual
+            | TokenType::BangEqual
+            | TokenType::LeftCaret
+            | TokenType::RightCaret
+            | TokenType::LessEqual
+            | TokenType::GreaterEqual
+    )
+}
+
+/// Type of a possibly nested field, `a.b` is `b` in the struct of `a`.
+fn field_type(fields: &[FieldDeclaration], path: &[QualifiedName]) -> Option<Type> {
+    let (name, rest) = path.split_first()?;
+    let field = fields.iter().find(|f| &f.0 == name)?;
+    match (&field.1, rest.is_empty()) {
+        (ty, true) => Some(ty.clone()),
+        (Type::Struct(nested), false) => field_type(nested, rest),
+        _ => None,
     }
 }
 
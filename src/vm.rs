@@ -1,137 +1,607 @@
-use crate::bytecode::{Chunk, Opcode};
-use crate::parser::{Object, QualifiedName};
+use crate::bytecode::{Chunk, Opcode, Prototype, Span};
+use crate::formatter::{EnumEncoding, JsonFmt};
+use crate::parser::{Object, Type};
+use crate::trace::{Phase, Tracer};
+use std::fmt;
+use std::rc::Rc;
+
+/// Error raised while evaluating. `VM::run` returns it wrapped in the frames
+/// it unwound, innermost first.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RuntimeError {
+    /// An instruction needed more values than the stack holds.
+    StackUnderflow,
+    /// A value of the wrong kind, named as by `Object::kind`.
+    TypeMismatch {
+        expected: &'static str,
+        got: &'static str,
+    },
+    /// An operator applied to kinds it does not support.
+    InvalidOperands {
+        op: &'static str,
+        left: &'static str,
+        right: &'static str,
+    },
+    NotCallable(&'static str),
+    InvalidPattern {
+        pattern: String,
+        reason: String,
+    },
+    /// A struct field constraint does not hold, the message is located.
+    ConstraintFailed(String),
+    /// A failed `assert`, the message is located.
+    AssertionFailed(String),
+    /// An instruction, local, upvalue or constant the chunk does not have.
+    InvalidBytecode(String),
+    /// Calls nested deeper than `MAX_DEPTH`.
+    StackOverflow,
+    /// Raised while the frame of the named function ran the instruction at
+    /// `span`.
+    Frame {
+        name: String,
+        span: Span,
+        error: Box<RuntimeError>,
+    },
+}
+
+impl RuntimeError {
+    /// The error under the frames it unwound.
+    pub fn cause(&self) -> &RuntimeError {
+        match self {
+            RuntimeError::Frame { error, .. } => error.cause(),
+            error => error,
+        }
+    }
+
+    /// Function names and spans of the unwound frames, innermost first.
+    pub fn trace(&self) -> Vec<(&str, Span)> {
+        let mut trace = vec![];
+        let mut error = self;
+        while let RuntimeError::Frame {
+            name,
+            span,
+            error: inner,
+        } = error
+        {
+            trace.push((&**name, *span));
+            error = inner;
+        }
+        trace.reverse();
+        trace
+    }
+}
+
+/// The message, located at the innermost frame unless it already is, then
+/// the calls leading to it when it was raised in a function.
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let trace = self.trace();
+        match self.cause() {
+            RuntimeError::ConstraintFailed(message) | RuntimeError::AssertionFailed(message) => {
+                write!(f, "{}", message)?
+            }
+            error => {
+                write!(f, "{}: ", trace.first().map(|t| t.1).unwrap_or_default())?;
+                match error {
+                    RuntimeError::StackUnderflow => write!(f, "Stack underflow")?,
+                    RuntimeError::TypeMismatch { expected, got } => {
+                        write!(f, "Expected '{}', got '{}'", expected, got)?
+                    }
+                    RuntimeError::InvalidOperands { op, left, right } => {
+                        write!(f, "Cannot apply '{}' to '{}' and '{}'", op, left, right)?
+                    }
+                    RuntimeError::NotCallable(kind) => write!(f, "Cannot call '{}'", kind)?,
+                    RuntimeError::InvalidPattern { pattern, reason } => {
+                        write!(f, "Invalid pattern '{}': {}", pattern, reason)?
+                    }
+                    RuntimeError::InvalidBytecode(missing) => {
+                        write!(f, "Invalid bytecode, no {}", missing)?
+                    }
+                    RuntimeError::StackOverflow => {
+                        write!(f, "Stack overflow, calls nest deeper than {}", MAX_DEPTH)?
+                    }
+                    _ => unreachable!("located errors are written above"),
+                }
+            }
+        }
+        if trace.len() > 1 {
+            for (name, span) in trace {
+                write!(f, "\n  in {} at {}", name, span)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What the frame does after an instruction.
+enum Step {
+    Next,
+    Call(Object, Vec<Object>),
+    Return(Object),
+}
+
+/// Calls a program can nest, each one runs on the Rust stack.
+const MAX_DEPTH: usize = 1024;
 
 pub struct VM {
-    frames: Vec<Frame>,
+    frame: Frame,
 }
 
 struct Frame {
-    chunk: Chunk,
+    function: Rc<Prototype>,
     ip: usize,
+    /// Calls enclosing the frame, 0 for `main`.
+    depth: usize,
 
     locals: Vec<Object>,
+    /// Shared with the closure being run.
+    upvalues: Rc<Vec<Object>>,
     stack: Vec<Object>,
 
-    name: String,
-    debug: bool,
+    tracer: Tracer,
 }
 
 impl Frame {
-    pub fn init(chunk: Chunk, debug: bool) -> Self {
-        Frame {
+    pub fn init(chunk: Chunk, tracer: Tracer) -> Self {
+        let function = Prototype {
+            arity: 0,
+            name: "main".to_string(),
             chunk,
+            ty: Type::Infer,
+        };
+        Frame {
+            function: Rc::new(function),
             ip: 0,
+            depth: 0,
             locals: vec![],
+            upvalues: Rc::default(),
             stack: vec![],
-            name: "main".to_string(),
-            debug,
+            tracer,
         }
     }
 
-    fn pop(&mut self) -> Object {
-        self.stack.pop().unwrap()
+    fn pop(&mut self) -> Result<Object, RuntimeError> {
+        self.stack.pop().ok_or(RuntimeError::StackUnderflow)
     }
 
-    pub fn run(&mut self) -> Object {
-        loop {
-            let op = self.chunk.code[self.ip].clone();
-            self.ip += 1;
+    /// Errors are wrapped with the frame and the span of the failing
+    /// instruction.
+    pub fn run(&mut self) -> Result<Object, RuntimeError> {
+        self.execute().map_err(|error| RuntimeError::Frame {
+            name: self.function.name.clone(),
+            span: self
+                .function
+                .chunk
+                .spans
+                .get(self.ip.saturating_sub(1))
+                .unwrap_or_default(),
+            error: Box::new(error),
+        })
+    }
+
+    fn local(&self, i: usize) -> Result<&Object, RuntimeError> {
+        self.locals.get(i).ok_or_else(|| invalid("local", i))
+    }
+
+    fn upvalue(&self, i: usize) -> Result<&Object, RuntimeError> {
+        self.upvalues.get(i).ok_or_else(|| invalid("upvalue", i))
+    }
+
+    fn constant(&self, i: u32) -> Result<&Object, RuntimeError> {
+        self.function
+            .chunk
+            .constants
+            .get(i as usize)
+            .ok_or_else(|| invalid("constant", i))
+    }
+
+    fn string_constant(&self, i: u32) -> Result<&str, RuntimeError> {
+        match self.constant(i)? {
+            Object::String(s) => Ok(s),
+            _ => Err(invalid("string constant", i)),
+        }
+    }
 
-            if self.debug {
-                println!("[Frame {}] executing {:?}", self.name, op);
+    fn execute(&mut self) -> Result<Object, RuntimeError> {
+        loop {
+            // Calls run here rather than in `step`, whose frame is large in
+            // debug builds, so each call only adds a small frame to the Rust
+            // stack.
+            match self.step()? {
+                Step::Next => {}
+                Step::Call(fun, args) => {
+                    let result = call(fun, args, self.depth + 1, &self.tracer)?;
+                    self.stack.push(result);
+                }
+                Step::Return(value) => return Ok(value),
             }
 
-            match op {
-                Opcode::Pop => {
-                    self.pop();
+            self.tracer.event(Phase::Vm, "state", || {
+                vec![
+                    ("frame", self.function.name.clone()),
+                    ("stack", format!("{:?}", self.stack)),
+                    ("locals", format!("{:?}", self.locals)),
+                ]
+            });
+        }
+    }
+
+    /// Runs the next instruction.
+    fn step(&mut self) -> Result<Step, RuntimeError> {
+        let op = *self
+            .function
+            .chunk
+            .code
+            .get(self.ip)
+            .ok_or_else(|| invalid("instruction", self.ip))?;
+        self.ip += 1;
+
+        self.tracer.event(Phase::Vm, "execute", || {
+            vec![
+                ("frame", self.function.name.clone()),
+                (
+                    "span",
+                    self.function
+                        .chunk
+                        .spans
+                        .get(self.ip - 1)
+                        .unwrap_or_default()
+                        .to_string(),
+                ),
+                ("op", format!("{:?}", op)),
+            ]
+        });
+
+        match op {
+            Opcode::Pop => {
+                self.pop()?;
+            }
+            Opcode::Nil => {
+                self.stack.push(Object::Nil);
+            }
+            Opcode::Load(i) => {
+                let val = self.local(i as usize)?.clone();
+                self.stack.push(val);
+            }
+            Opcode::Store(i) => {
+                let top = self.pop()?;
+                if self.locals.len() <= i as usize {
+                    self.locals.resize(i as usize + 1, Object::Nil);
                 }
-                Opcode::Nil => {
-                    self.stack.push(Object::Nil);
+                self.locals[i as usize] = top;
+            }
+            Opcode::Constant(i) => {
+                let val = self.constant(i)?.clone();
+                self.stack.push(val);
+            }
+            Opcode::Struct(s) => {
+                let mut fields = vec![];
+                for _ in 0..s {
+                    let name = match self.pop()? {
+                        Object::String(name) => name,
+                        name => return Err(mismatch("String", &name)),
+                    };
+                    let val = self.pop()?;
+                    fields.push((name, val));
                 }
-                Opcode::Load(i) => {
-                    let val = self.locals[i as usize].clone();
-                    self.stack.push(val);
+                self.stack.push(Object::Struct(fields.into()))
+            }
+            Opcode::Array(s) => {
+                let mut arr = vec![];
+                for _ in 0..s {
+                    arr.push(self.pop()?);
                 }
-                Opcode::Store(i) => {
-                    let top = self.pop();
-                    if self.locals.len() <= i as usize {
-                        self.locals.push(top);
+                self.stack.push(Object::Array(arr.into()))
+            }
+            Opcode::Map(s) => {
+                let mut entries: Vec<(Rc<str>, Object)> = vec![];
+                for _ in 0..s {
+                    let key = match self.pop()? {
+                        Object::String(key) => key,
+                        key => return Err(mismatch("String", &key)),
+                    };
+                    let value = self.pop()?;
+                    if let Some(entry) = entries.iter_mut().find(|e| e.0 == key) {
+                        entry.1 = value;
                     } else {
-                        self.locals[i as usize] = top;
+                        entries.push((key, value));
                     }
                 }
-                Opcode::Constant(i) => self.stack.push(self.chunk.constants[i as usize].clone()),
-                Opcode::Struct(s) => {
-                    let mut fields = vec![];
-                    for _ in 0..s {
-                        let field = self.pop();
-                        let val = self.pop();
-                        fields.push((QualifiedName(field.as_str()), val));
+                self.stack.push(Object::Map(entries.into()))
+            }
+            Opcode::Index => {
+                let value = self.pop()?;
+                let index = self.pop()?;
+                let item = match (value, index) {
+                    (Object::Array(items), Object::Num(i)) if i >= 0.0 && i.fract() == 0.0 => {
+                        items.get(i as usize).cloned()
                     }
-                    self.stack.push(Object::Struct(fields))
-                }
-                Opcode::Array(s) => {
-                    let mut arr = vec![];
-                    for _ in 0..s {
-                        arr.push(self.pop());
-                    }
-                    self.stack.push(Object::Array(arr))
-                }
-                Opcode::Call => {
-                    let fun = self.pop();
-                    if let Object::Function(arity, name, chunk, _) = fun {
-                        let mut args = vec![];
-                        for _ in 0..arity {
-                            args.push(self.pop());
-                        }
-                        let mut frame = Frame {
-                            chunk,
-                            ip: 0,
-                            locals: args,
-                            stack: vec![],
-                            name,
-                            debug: self.debug,
-                        };
-                        self.stack.push(frame.run());
-                    } else {
-                        eprintln!("Could not execute {:?}", fun);
+                    (Object::Map(entries), Object::String(key)) => {
+                        entries.iter().find(|e| e.0 == key).map(|e| e.1.clone())
                     }
+                    _ => None,
+                };
+                self.stack.push(item.unwrap_or(Object::Nil));
+            }
+            Opcode::Next(slot, exit) => {
+                let slot = slot as usize;
+                let cursor = match self.local(slot + 1)? {
+                    Object::Num(cursor) => *cursor as usize,
+                    cursor => return Err(mismatch("Number", cursor)),
+                };
+                let entry = match self.local(slot)? {
+                    Object::Array(items) => items
+                        .get(cursor)
+                        .map(|item| (Object::Num(cursor as f64), item.clone())),
+                    Object::Map(entries) => entries
+                        .get(cursor)
+                        .map(|e| (Object::String(e.0.clone()), e.1.clone())),
+                    collection => return Err(mismatch("Array", collection)),
+                };
+                if let Some((key, value)) = entry {
+                    self.locals[slot + 1] = Object::Num(cursor as f64 + 1.0);
+                    self.stack.push(value);
+                    self.stack.push(key);
+                } else {
+                    self.ip = exit as usize;
                 }
-                Opcode::Add => {
-                    let left = self.pop();
-                    let right = self.pop();
-                    self.stack.push(left + right);
+            }
+            Opcode::Append(slot) => {
+                let value = self.pop()?;
+                let array = self
+                    .locals
+                    .get_mut(slot as usize)
+                    .ok_or_else(|| invalid("local", slot))?;
+                match array {
+                    // The array is only referenced by the local, so it
+                    // is not copied.
+                    Object::Array(items) => Rc::make_mut(items).push(value),
+                    array => return Err(mismatch("Array", array)),
                 }
-                Opcode::Multiply => {
-                    let left = self.pop();
-                    let right = self.pop();
-                    self.stack.push(left * right);
+            }
+            Opcode::LoadUpvalue(i) => {
+                let val = self.upvalue(i as usize)?.clone();
+                self.stack.push(val);
+            }
+            Opcode::Closure(i) => match self.constant(i)? {
+                Object::Function(prototype, _) => {
+                    let captured = prototype
+                        .chunk
+                        .upvalues
+                        .iter()
+                        .map(|u| {
+                            if u.is_local {
+                                self.local(u.index).cloned()
+                            } else {
+                                self.upvalue(u.index).cloned()
+                            }
+                        })
+                        .collect::<Result<_, _>>()?;
+                    let closure = Object::Function(prototype.clone(), Rc::new(captured));
+                    self.stack.push(closure);
                 }
-                Opcode::Return => {
-                    return self.stack[0].clone();
+                constant => return Err(mismatch("Fn", constant)),
+            },
+            Opcode::Call => {
+                let fun = self.pop()?;
+                let arity = match &fun {
+                    Object::Function(prototype, _) => prototype.arity,
+                    Object::NativeFunction(native) => native.arity,
+                    _ => 0,
+                };
+                let mut args = vec![];
+                for _ in 0..arity {
+                    args.push(self.pop()?);
                 }
+                args.reverse();
+                return Ok(Step::Call(fun, args));
             }
-
-            if self.debug {
-                println!("[Frame {}] stack: {:?}", self.name, self.stack);
-                println!("[Frame {}] locals: {:?}", self.name, self.locals);
+            Opcode::Variant(i) => {
+                let payload = self.pop()?;
+                let tag = match self.constant(i)? {
+                    Object::String(tag) => tag.clone(),
+                    tag => return Err(mismatch("String", tag)),
+                };
+                self.stack
+                    .push(Object::Variant(tag, Some(Rc::new(payload))));
+            }
+            Opcode::Unwrap => match self.pop()? {
+                Object::Variant(_, Some(payload)) => self.stack.push(Rc::unwrap_or_clone(payload)),
+                value => return Err(mismatch("Variant", &value)),
+            },
+            Opcode::Jump(target) => {
+                self.ip = target as usize;
+            }
+            Opcode::JumpIfFalse(target) => {
+                if self.stack.last() == Some(&Object::Bool(false)) {
+                    self.ip = target as usize;
+                }
+            }
+            Opcode::JumpIfTrue(target) => {
+                if self.stack.last() == Some(&Object::Bool(true)) {
+                    self.ip = target as usize;
+                }
+            }
+            Opcode::JumpIfNotVariant(i, target) => {
+                let value = self.pop()?;
+                let tag = self.string_constant(i)?;
+                if !matches!(value, Object::Variant(t, _) if &*t == tag) {
+                    self.ip = target as usize;
+                }
+            }
+            Opcode::JumpIfNotKind(i, target) => {
+                let value = self.pop()?;
+                if value.kind().name() != self.string_constant(i)? {
+                    self.ip = target as usize;
+                }
+            }
+            Opcode::Add => self.binary("+", Object::checked_add)?,
+            Opcode::Merge => self.binary("//", Object::merge)?,
+            Opcode::DeepMerge => self.binary("/\\", Object::deep_merge)?,
+            Opcode::Update(i) => {
+                let base = self.pop()?;
+                let overrides = self.pop()?;
+                let error = RuntimeError::InvalidOperands {
+                    op: "..",
+                    left: base.kind().name(),
+                    right: overrides.kind().name(),
+                };
+                let updated = base.update(overrides, self.constant(i)?).ok_or(error)?;
+                self.stack.push(updated);
+            }
+            Opcode::Multiply => self.binary("*", Object::checked_mul)?,
+            Opcode::Negate => match self.pop()? {
+                Object::Num(n) => self.stack.push(Object::Num(-n)),
+                value => return Err(mismatch("Number", &value)),
+            },
+            Opcode::Not => {
+                let value = self.pop()?;
+                self.stack.push(Object::Bool(value != Object::Bool(true)));
+            }
+            Opcode::Equal => {
+                let left = self.pop()?;
+                let right = self.pop()?;
+                self.stack.push(Object::Bool(left == right));
+            }
+            Opcode::Less => {
+                let left = self.pop()?;
+                let right = self.pop()?;
+                self.stack
+                    .push(Object::Bool(compare(left, right, |o| o.is_lt())));
+            }
+            Opcode::Greater => {
+                let left = self.pop()?;
+                let right = self.pop()?;
+                self.stack
+                    .push(Object::Bool(compare(left, right, |o| o.is_gt())));
+            }
+            Opcode::Check(path, message) => {
+                let predicate = self.pop()?;
+                let value = match self.constant(path)? {
+                    Object::Array(path) => {
+                        let mut value = self.stack.last();
+                        for name in path.iter() {
+                            let name = match name {
+                                Object::String(name) => name,
+                                name => return Err(mismatch("String", name)),
+                            };
+                            value = value.and_then(|v| v.field(name));
+                        }
+                        value
+                    }
+                    path => return Err(mismatch("Array", path)),
+                };
+                if let Some(value) = value.filter(|v| **v != Object::Nil).cloned() {
+                    let holds = call(predicate, vec![value.clone()], self.depth + 1, &self.tracer)?;
+                    if holds != Object::Bool(true) {
+                        let formatter = JsonFmt::new(false, EnumEncoding::String);
+                        return Err(RuntimeError::ConstraintFailed(format!(
+                            "{}, got {}",
+                            self.string_constant(message)?,
+                            formatter.format(&value)
+                        )));
+                    }
+                }
+            }
+            Opcode::Assert(i) => {
+                let condition = self.pop()?;
+                let message = self.pop()?;
+                if condition != Object::Bool(true) {
+                    let location = self.string_constant(i)?;
+                    return Err(RuntimeError::AssertionFailed(match message {
+                        Object::String(message) => format!("{}: {}", location, message),
+                        _ => location.to_string(),
+                    }));
+                }
+            }
+            Opcode::Return => {
+                return self.pop().map(Step::Return);
             }
         }
+        Ok(Step::Next)
+    }
+
+    /// Pops the left then the right operand, and pushes the result of `op`.
+    fn binary(
+        &mut self,
+        name: &'static str,
+        op: fn(Object, Object) -> Option<Object>,
+    ) -> Result<(), RuntimeError> {
+        let left = self.pop()?;
+        let right = self.pop()?;
+        let error = RuntimeError::InvalidOperands {
+            op: name,
+            left: left.kind().name(),
+            right: right.kind().name(),
+        };
+        self.stack.push(op(left, right).ok_or(error)?);
+        Ok(())
     }
 }
 
-impl VM {
-    pub fn init(chunk: Chunk, debug: bool) -> Self {
-        VM {
-            frames: vec![Frame::init(chunk, debug)],
+pub fn mismatch(expected: &'static str, got: &Object) -> RuntimeError {
+    RuntimeError::TypeMismatch {
+        expected,
+        got: got.kind().name(),
+    }
+}
+
+fn invalid(missing: &str, i: impl fmt::Display) -> RuntimeError {
+    RuntimeError::InvalidBytecode(format!("{} {}", missing, i))
+}
+
+/// Orders numbers or strings, anything else is unordered.
+fn compare(left: Object, right: Object, test: fn(std::cmp::Ordering) -> bool) -> bool {
+    let ordering = match (left, right) {
+        (Object::Num(a), Object::Num(b)) => a.partial_cmp(&b),
+        (Object::String(a), Object::String(b)) => Some(a.cmp(&b)),
+        _ => None,
+    };
+    ordering.map(test).unwrap_or(false)
+}
+
+/// Runs a function in a new frame, at `depth` calls from `main`. Natives call
+/// back here for the functions they are given.
+fn call(
+    fun: Object,
+    args: Vec<Object>,
+    depth: usize,
+    tracer: &Tracer,
+) -> Result<Object, RuntimeError> {
+    if depth > MAX_DEPTH {
+        return Err(RuntimeError::StackOverflow);
+    }
+    match fun {
+        Object::Function(function, upvalues) => {
+            let mut locals = args;
+            // The callee follows its arguments, see `Chunk::function`.
+            locals.push(Object::Function(function.clone(), upvalues.clone()));
+            let mut frame = Frame {
+                function,
+                ip: 0,
+                depth,
+                locals,
+                upvalues,
+                stack: vec![],
+                tracer: tracer.clone(),
+            };
+            frame.run()
+        }
+        Object::NativeFunction(native) => {
+            (native.fun)(args, &mut |f, args| call(f, args, depth + 1, tracer))
         }
+        _ => Err(RuntimeError::NotCallable(fun.kind().name())),
     }
+}
 
-    fn frame(&mut self) -> &mut Frame {
-        self.frames.last_mut().unwrap()
+impl VM {
+    pub fn init(chunk: Chunk, tracer: Tracer) -> Self {
+        VM {
+            frame: Frame::init(chunk, tracer),
+        }
     }
 
-    pub fn run(&mut self) -> Object {
-        self.frame().run()
+    pub fn run(&mut self) -> Result<Object, RuntimeError> {
+        self.frame.run()
     }
 }
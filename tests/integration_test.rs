@@ -32,3 +32,988 @@ fn third_test() {
     {\"home\":\"/home/bob\",\"public_key\":\"/home/bob/.ssh/id_ed25519.pub\",\"private_key\":\"/home/bob/.ssh/id_ed25519\"},\
     {\"home\":\"/home/alice\",\"public_key\":\"/home/alice/.ssh/id_ed25519.pub\",\"private_key\":\"/home/alice/.ssh/id_ed25519\"}]\n");
 }
+
+#[test]
+fn fourth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00004.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "{\"apiVersion\":\"apps/v1\",\
+    \"metadata\":{\"name\":\"web\",\"labels\":{\"app\":\"web\",\"tier\":\"front\"}},\
+    \"spec\":{\"replicas\":3,\"template\":{\"metadata\":{\"name\":\"web-pod\"}}}}\n"
+    );
+}
+
+#[test]
+fn fifth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00005.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:8: Expected 'String', got 'Num'\n"
+    );
+}
+
+#[test]
+fn sixth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00006.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "[{\"name\":\"api\",\"replicas\":1,\"image\":\"api:1.0\",\"env\":\"dev\"},\
+    {\"name\":\"api\",\"replicas\":3,\"image\":\"api:1.0\",\"env\":\"prod\"}]\n"
+    );
+}
+
+#[test]
+fn seventh_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00007.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:8: Expected 'Service', got 'String'\n"
+    );
+}
+
+#[test]
+fn eighth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00008.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "{\"replicas\":2,\"resources\":{\"cpu\":\"500m\",\"memory\":\"1Gi\"},\"env\":\"prod\"}\n"
+    );
+}
+
+#[test]
+fn ninth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00009.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "{\"replicas\":2,\"resources\":{\"memory\":\"1Gi\"},\"env\":\"prod\"}\n"
+    );
+}
+
+#[test]
+fn tenth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00010.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:19: Conflicting types for field 'memory': 'String' and 'Num'\n"
+    );
+}
+
+#[test]
+fn eleventh_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00011.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "[{\"name\":\"api\",\"replicas\":1,\"timeout\":30,\"image\":null,\"ports\":[80,443],\
+    \"resources\":{\"cpu\":\"100m\",\"memory\":null}},\
+    {\"name\":\"web\",\"replicas\":3,\"timeout\":30,\"image\":\"web:2\",\"ports\":[80,443],\
+    \"resources\":{\"cpu\":\"100m\",\"memory\":\"1Gi\"}}]\n"
+    );
+
+    let out = Command::new("target/debug/mia")
+        .arg("--skip-nil")
+        .arg("tests/samples/00011.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "[{\"name\":\"api\",\"replicas\":1,\"timeout\":30,\"ports\":[80,443],\
+    \"resources\":{\"cpu\":\"100m\"}},\
+    {\"name\":\"web\",\"replicas\":3,\"timeout\":30,\"image\":\"web:2\",\"ports\":[80,443],\
+    \"resources\":{\"cpu\":\"100m\",\"memory\":\"1Gi\"}}]\n"
+    );
+}
+
+#[test]
+fn twelfth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00012.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:7: Missing field 'name' in 'Deployment'\n"
+    );
+}
+
+#[test]
+fn thirteenth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00013.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:10: Unknown field 'privte_key' in 'Kube', did you mean 'private_key'?\n"
+    );
+}
+
+#[test]
+fn fourteenth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00014.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:9: Field 'name' is given twice in 'metadata'\n"
+    );
+}
+
+#[test]
+fn fifteenth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00015.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:7: Unknown field 'shell' in 'Kube'\n"
+    );
+}
+
+#[test]
+fn sixteenth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00016.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "{\"source\":{\"tag\":\"Git\",\"value\":\"https://example.com/app.git\"},\
+    \"kind\":\"git: https://example.com/app.git\",\
+    \"ports\":[{\"number\":80,\"protocol\":\"Tcp\"},{\"number\":53,\"protocol\":\"Udp\"}]}\n"
+    );
+
+    let out = Command::new("target/debug/mia")
+        .arg("--enum-encoding=tagged")
+        .arg("tests/samples/00016.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "{\"source\":{\"tag\":\"Git\",\"value\":\"https://example.com/app.git\"},\
+    \"kind\":\"git: https://example.com/app.git\",\
+    \"ports\":[{\"number\":80,\"protocol\":{\"tag\":\"Tcp\"}},{\"number\":53,\"protocol\":{\"tag\":\"Udp\"}}]}\n"
+    );
+
+    let out = Command::new("target/debug/mia")
+        .arg("--enum-encoding=untagged")
+        .arg("tests/samples/00016.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "{\"source\":\"https://example.com/app.git\",\
+    \"kind\":\"git: https://example.com/app.git\",\
+    \"ports\":[{\"number\":80,\"protocol\":\"Tcp\"},{\"number\":53,\"protocol\":\"Udp\"}]}\n"
+    );
+}
+
+#[test]
+fn seventeenth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00017.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:9: Non-exhaustive match, missing 'Udp', 'Sctp'\n"
+    );
+}
+
+#[test]
+fn eighteenth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00018.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:13: Unknown variant 'Udpp' in 'Protocol', did you mean 'Udp'?\n"
+    );
+}
+
+#[test]
+fn nineteenth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00019.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "{\"port\":{\"first\":\"http\",\"second\":80},\
+    \"image\":{\"version\":2,\"item\":{\"name\":\"image\",\"value\":\"nginx:1.25\"}},\
+    \"replicas\":{\"name\":\"replicas\",\"value\":3}}\n"
+    );
+}
+
+#[test]
+fn twentieth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00020.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:15: Expected 'Named<String>', got 'Named<Num>'\n"
+    );
+}
+
+#[test]
+fn twenty_first_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00021.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "{\"name\":\"api\",\"labels\":{\"team\":\"platform\",\"tier\":\"frontend\",\"app\":\"api\"},\
+    \"env\":{},\"selectors\":[\"team=platform\",\"tier=frontend\",\"app=api\"],\
+    \"owner\":\"platform\",\"ports\":[80,444]}\n"
+    );
+}
+
+#[test]
+fn twenty_second_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00022.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:6: Expected 'Map<String, String>', got 'Map<String, Num>'\n"
+    );
+}
+
+#[test]
+fn twenty_third_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00023.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "{\"web\":{\"cpu\":\"100m\",\"memory\":\"256Mi\"},\"worker\":{\"cpu\":\"500m\"}}\n"
+    );
+}
+
+#[test]
+fn twenty_fourth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00024.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "[{\"name\":\"http\",\"port\":80,\"targetPort\":\"web\"},\
+    {\"name\":\"metrics\",\"port\":9090,\"targetPort\":9090}]\n"
+    );
+}
+
+#[test]
+fn twenty_fifth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00025.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:5: Non-exhaustive match, missing 'String'\n"
+    );
+}
+
+#[test]
+fn twenty_sixth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00026.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "{\"name\":\"ada\",\"age\":36}\n"
+    );
+}
+
+#[test]
+fn twenty_seventh_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00027.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:10: Expected 2 arguments, got 3\n"
+    );
+}
+
+#[test]
+fn twenty_eighth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00028.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:1: Function 'port' must return 'Num'\n"
+    );
+}
+
+#[test]
+fn twenty_ninth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00029.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "[{\"name\":\"svc-api\",\"image\":\"ghcr.io/acme/svc-api\",\"port\":8080},\
+    {\"name\":\"svc-worker\",\"image\":\"ghcr.io/acme/svc-worker\",\"port\":8080}]\n"
+    );
+}
+
+#[test]
+fn thirtieth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00030.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:1: Cannot infer the type of 'x'\n"
+    );
+}
+
+#[test]
+fn thirty_first_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00031.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "{\"names\":[\"api\",\"cron-worker\",\"worker\"],\
+    \"hosts\":[\"api.svc\",\"worker.svc\",\"cron.worker.svc\"],\
+    \"env\":\"API_WORKER_CRON-WORKER\",\"workers\":[\"cron-worker\"],\
+    \"ports\":[8080,8081,8082,9090,9091],\"tags\":[\"a\",\"b\",\"3\"],\
+    \"count\":3,\"timeout\":30,\"invalid\":null}\n"
+    );
+}
+
+#[test]
+fn thirty_second_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00032.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:1: 'frobnicate' is not exported by 'String'\n"
+    );
+}
+
+#[test]
+fn thirty_third_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00033.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "{\"user\":\"YWRtaW4=\",\"password\":\"czNjcjN0IT8=\",\"decoded\":\"hello world\",\
+    \"invalid\":null,\
+    \"checksum\":\"776530bb9f97f489746afe927013f7b659ac108e425ca071bb13efe63c130f98\",\
+    \"hex\":\"6d6961\",\"query\":\"a%20b%26c%3Dd%2F%C3%A9\",\
+    \"id\":\"2ed6657d-e927-568b-95e1-2665a8aea6a2\"}\n"
+    );
+}
+
+#[test]
+fn thirty_fourth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00034.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "{\"web\":{\"name\":\"web-1\",\"port\":8080,\"replicas\":3,\"limits\":{\"cpu\":2}},\
+    \"api\":{\"name\":\"api\",\"port\":8080,\"replicas\":3,\"limits\":{\"cpu\":2}},\
+    \"checks\":[true,false,true,true,true],\"valid\":true}\n"
+    );
+}
+
+#[test]
+fn thirty_fifth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00035.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:10: Field 'port' of 'Service' does not satisfy the constraint at line 3, got 70000\n"
+    );
+}
+
+#[test]
+fn thirty_sixth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00036.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:5: Field 'name' of 'Service' does not match '^[a-z][a-z0-9-]*$', got \"Web Server\"\n"
+    );
+}
+
+#[test]
+fn thirty_seventh_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00037.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:3: Assertion failed: expected three ports\n"
+    );
+}
+
+#[test]
+fn thirty_eighth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00038.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "{\"name\":\"web\",\"replicas\":1,\"strategy\":\"RollingUpdate\",\"labels\":{\"app\":\"web\"},\
+    \"containers\":[{\"image\":\"nginx:1.27\",\"pullPolicy\":\"IfNotPresent\",\
+    \"ports\":[{\"containerPort\":8080,\"protocol\":\"TCP\"}],\
+    \"env\":[{\"name\":\"MODE\",\"value\":\"production\"}]}],\
+    \"runs-on\":\"ubuntu-latest\",\"$comment\":\"rendered by mia\"}\n"
+    );
+}
+
+#[test]
+fn thirty_ninth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00039.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:6: Field 'replicas' of 'Deployment' does not satisfy the schema at 00038.json#/properties/replicas, got 0\n"
+    );
+}
+
+#[test]
+fn fortieth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00040.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:1: 'Service' is not exported by '00038.json'\n"
+    );
+}
+
+#[test]
+fn forty_first_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("import-schema")
+        .arg("tests/samples/00038.json")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        std::fs::read_to_string("tests/samples/00041.m").unwrap()
+    );
+}
+
+#[test]
+fn forty_second_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("schema")
+        .arg("tests/samples/00034.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        std::fs::read_to_string("tests/samples/00042.json").unwrap()
+    );
+}
+
+#[test]
+fn forty_third_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("schema")
+        .arg("--enum-encoding=tagged")
+        .arg("tests/samples/00016.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        std::fs::read_to_string("tests/samples/00043.json").unwrap()
+    );
+}
+
+#[test]
+fn forty_fourth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("rust")
+        .arg("tests/samples/00044.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        std::fs::read_to_string("tests/samples/00044.rs").unwrap()
+    );
+}
+
+#[test]
+fn forty_fifth_test() {
+    assert_eq!(
+        mia::rust("tests/samples/00016.m", mia::EnumEncoding::Tagged),
+        std::fs::read_to_string("tests/samples/00045.rs").unwrap()
+    );
+}
+
+#[test]
+fn forty_sixth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("typescript")
+        .arg("tests/samples/00044.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        std::fs::read_to_string("tests/samples/00046.d.ts").unwrap()
+    );
+}
+
+#[test]
+fn forty_seventh_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("typescript")
+        .arg("--enum-encoding=untagged")
+        .arg("tests/samples/00047.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        std::fs::read_to_string("tests/samples/00047.d.ts").unwrap()
+    );
+}
+
+#[test]
+fn forty_eighth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00048.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:7: Field 'port' of 'Service' does not satisfy the constraint at line 3, got 70000
+  in service at hello.m:7:12
+  in web at hello.m:11:19
+  in main at hello.m:14:19\n"
+    );
+    assert_eq!(out.status.code(), Some(65));
+}
+
+#[test]
+fn forty_ninth_test() {
+    let error = mia::run_file(
+        "tests/samples/00049.m",
+        &mia::Tracer::default(),
+        false,
+        mia::EnumEncoding::String,
+    )
+    .unwrap_err();
+    assert_eq!(
+        error.cause(),
+        &mia::RuntimeError::InvalidPattern {
+            pattern: "(web".to_string(),
+            reason: "missing ')'".to_string()
+        }
+    );
+    assert_eq!(
+        error.trace(),
+        vec![
+            ("valid", mia::Span { line: 4, col: 19 }),
+            ("main", mia::Span { line: 7, col: 19 })
+        ]
+    );
+    assert_eq!(
+        error.to_string(),
+        "hello.m:4:19: Invalid pattern '(web': missing ')'
+  in valid at hello.m:4:19
+  in main at hello.m:7:19"
+    );
+}
+
+#[test]
+fn fiftieth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("--trace=vm")
+        .arg("tests/samples/00050.m")
+        .output()
+        .unwrap();
+    let trace: Vec<String> = String::from_utf8(out.stderr)
+        .unwrap()
+        .lines()
+        .filter(|line| line.starts_with("[vm] execute"))
+        .map(|line| line.to_string())
+        .collect();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "42\n");
+    assert_eq!(
+        trace,
+        vec![
+            "[vm] execute frame=main span=hello.m:1:12 op=Constant(0)",
+            "[vm] execute frame=main span=hello.m:1:5 op=Store(0)",
+            "[vm] execute frame=main span=hello.m:4:5 op=Constant(1)",
+            "[vm] execute frame=main span=hello.m:3:16 op=Load(0)",
+            "[vm] execute frame=main span=hello.m:3:21 op=Add",
+            "[vm] execute frame=main span=hello.m:3:9 op=Return",
+        ]
+    );
+}
+
+#[test]
+fn fifty_first_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("disasm")
+        .arg("tests/samples/00051.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        std::fs::read_to_string("tests/samples/00051.txt").unwrap()
+    );
+}
+
+#[test]
+fn fifty_second_test() {
+    let path = std::env::temp_dir().join("mia-fifty-second-test.jsonl");
+    let out = Command::new("target/debug/mia")
+        .arg("--trace=scanner,compiler")
+        .arg("--trace-format=json")
+        .arg(format!("--trace-file={}", path.display()))
+        .arg("tests/samples/00050.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "42\n");
+    assert_eq!(String::from_utf8(out.stderr).unwrap(), "");
+    let trace = std::fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = trace.lines().collect();
+    assert_eq!(
+        lines[0],
+        r#"{"phase":"scanner","event":"token","kind":"Let","lexeme":"let","line":"1","col":"1"}"#
+    );
+    assert!(lines.contains(
+        &r#"{"phase":"compiler","event":"instruction","offset":"4","span":"hello.m:3:21","op":"Add"}"#
+    ));
+    assert!(lines.iter().all(|line| !line.contains(r#""phase":"vm""#)));
+}
+
+#[test]
+fn fifty_third_test() {
+    let path = std::env::temp_dir().join("mia-fifty-third-test.m");
+    let mut script: String = (0..300).map(|i| format!("let v{} = {};\n", i, i)).collect();
+    script.push_str("pub let main = [v0, v255, v256, v299];\n");
+    std::fs::write(&path, script).unwrap();
+    let out = Command::new("target/debug/mia")
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "[0,255,256,299]\n");
+}
+
+#[test]
+fn fifty_fourth_test() {
+    let path = std::env::temp_dir().join("mia-fifty-fourth-test.m");
+    let params: Vec<String> = (0..256).map(|i| format!("p{}: Number", i)).collect();
+    let script = format!(
+        "fn wide({}): Number {{\n    return p0;\n}}\n\npub let main = 1;\n",
+        params.join(", ")
+    );
+    std::fs::write(&path, script).unwrap();
+    let out = Command::new("target/debug/mia")
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:1:4: Too many parameters in one function.\n"
+    );
+    assert_eq!(out.status.code(), Some(12));
+}
+
+#[test]
+fn fifty_fifth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("disasm")
+        .arg("tests/samples/00055.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        std::fs::read_to_string("tests/samples/00055.txt").unwrap()
+    );
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00055.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "[{\"name\":\"http\",\"number\":80},{\"name\":\"http\",\"number\":8080},{\"name\":\"metrics\",\"number\":8080}]\n"
+    );
+}
+
+#[test]
+fn fifty_sixth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00056.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "{\"dev\":[{\"replicas\":2,\"resources\":{\"cpu\":\"500m\",\"memory\":\"256Mi\"}},{\"replicas\":2,\"resources\":{\"cpu\":\"500m\",\"memory\":\"256Mi\"}}],\"prod\":{\"replicas\":2,\"resources\":{\"cpu\":\"500m\",\"memory\":\"1Gi\"}}}\n"
+    );
+}
+
+#[test]
+fn fifty_seventh_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00057.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "{\"kind\":\"Deployment\",\"meta\":{\"name\":\"api\",\"ns\":\"prod\"},\"spec\":{\"replicas\":3,\"image\":\"nginx\"}}\n"
+    );
+}
+
+#[test]
+fn fifty_eighth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00058.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:5: Cannot return outside of a function\n"
+    );
+}
+
+#[test]
+fn fifty_ninth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00059.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "{\"replicas\":3,\"names\":[\"web-0\",\"web-1\",\"web-2\"]}\n"
+    );
+}
+
+#[test]
+fn sixtieth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00060.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "{\"trimmed\":6,\"a\":1,\"name\":\"WEB\"}\n"
+    );
+}
+
+#[test]
+fn sixty_first_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00061.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:9: Field 'port' of 'Service' is not an integer, got 80.5\n"
+    );
+}
+
+#[test]
+fn sixty_second_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00062.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:3: Expected 'Schema00038.PullPolicy?', got 'String'\n"
+    );
+}
+
+#[test]
+fn sixty_third_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00063.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "{\"runs-on\":\"ubuntu-latest\",\"timeout-minutes\":10,\"steps\":{\"continue-on-error\":true}}\n"
+    );
+
+    let out = Command::new("target/debug/mia")
+        .arg("rust")
+        .arg("tests/samples/00063.m")
+        .output()
+        .unwrap();
+    assert!(String::from_utf8(out.stdout)
+        .unwrap()
+        .contains("    #[serde(rename = \"runs-on\")]\n    pub runs_on: String,\n"));
+}
+
+#[test]
+fn sixty_fourth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("schema")
+        .arg("tests/samples/00064.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        std::fs::read_to_string("tests/samples/00064.json").unwrap()
+    );
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:3: Part of the constraint on 'replicas' cannot be expressed in JSON Schema, it is not exported\n"
+    );
+}
+
+#[test]
+fn sixty_fifth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00065.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "{\"say \\\"hi\\\"\":\"C:\\\\temp\",\"digits\":\"^\\\\d+$\"}\n"
+    );
+}
+
+#[test]
+fn sixty_sixth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00066.m")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "null\n");
+}
+
+#[test]
+fn sixty_seventh_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00067.m")
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(68));
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "hello.m:7: Field 'meta' is not a nested struct\n"
+    );
+}
+
+#[test]
+fn sixty_eighth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("tests/samples/00068.m")
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(65));
+    assert!(String::from_utf8(out.stderr).unwrap().starts_with(
+        "hello.m:2:13: Stack overflow, calls nest deeper than 1024\n  in f at hello.m:2:13\n"
+    ));
+}
+
+#[test]
+fn sixty_ninth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("rust")
+        .arg("tests/samples/00016.m")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        std::fs::read_to_string("tests/samples/00069.rs").unwrap()
+    );
+    assert_eq!(
+        mia::rust("tests/samples/00016.m", mia::EnumEncoding::Untagged),
+        std::fs::read_to_string("tests/samples/00070.rs").unwrap()
+    );
+}
+
+#[test]
+fn seventieth_test() {
+    let out = Command::new("target/debug/mia")
+        .arg("import-schema")
+        .arg("tests/samples/00071.json")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        std::fs::read_to_string("tests/samples/00071.m").unwrap()
+    );
+}
@@ -0,0 +1,22 @@
+struct Labels {
+    app: String,
+    tier: String,
+}
+
+struct Deployment {
+    apiVersion: String,
+    metadata.name: String,
+    metadata.labels: Labels,
+    spec.replicas: Number,
+    spec.template.metadata.name: String,
+}
+
+let app = 'web';
+
+pub let main = Deployment {
+    apiVersion: 'apps/v1',
+    metadata.name: app,
+    metadata.labels: Labels { app: app, tier: 'front' },
+    spec.replicas: 3,
+    spec.template.metadata.name: app + '-pod',
+};
@@ -0,0 +1,9 @@
+struct Deployment {
+    metadata.name: String,
+    metadata.labels.app: String,
+}
+
+pub let main = Deployment {
+    metadata.name: 'web',
+    metadata.labels.app: 3,
+};
@@ -0,0 +1,18 @@
+struct Service {
+    name: String,
+    replicas: Number,
+    image: String,
+    env: String,
+}
+
+let base = Service {
+    name: 'api',
+    replicas: 1,
+    image: 'api:1.0',
+    env: 'dev',
+};
+
+pub let main = [
+    base,
+    Service { ..base, replicas: 3, env: 'prod' },
+];
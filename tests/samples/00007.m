@@ -0,0 +1,11 @@
+struct Service {
+    name: String,
+    env: String,
+}
+
+let base = 'api';
+
+pub let main = Service {
+    ..base,
+    env: 'prod',
+};
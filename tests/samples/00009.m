@@ -0,0 +1,23 @@
+struct Defaults {
+    replicas: Number,
+    resources.cpu: String,
+    resources.memory: String,
+}
+
+struct Overrides {
+    resources.memory: String,
+    env: String,
+}
+
+let team = Defaults {
+    replicas: 2,
+    resources.cpu: '500m',
+    resources.memory: '256Mi',
+};
+
+let prod = Overrides {
+    resources.memory: '1Gi',
+    env: 'prod',
+};
+
+pub let main = team // prod;
@@ -0,0 +1,19 @@
+struct Defaults {
+    replicas: Number,
+    resources.memory: String,
+}
+
+struct Overrides {
+    resources.memory: Number,
+}
+
+let team = Defaults {
+    replicas: 2,
+    resources.memory: '256Mi',
+};
+
+let prod = Overrides {
+    resources.memory: 1024,
+};
+
+pub let main = team /\ prod;
@@ -0,0 +1,14 @@
+struct Deployment {
+    name: String,
+    replicas: Number = 1,
+    timeout: Number = 30,
+    image: String?,
+    ports: Array<Number> = [80, 443],
+    resources.cpu: String = '100m',
+    resources.memory: String?,
+}
+
+pub let main = [
+    Deployment { name: 'api' },
+    Deployment { image: 'web:2', name: 'web', replicas: 3, resources.memory: '1Gi' },
+];
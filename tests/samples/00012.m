@@ -0,0 +1,9 @@
+struct Deployment {
+    name: String,
+    replicas: Number = 1,
+    image: String?,
+}
+
+pub let main = Deployment {
+    replicas: 2,
+};
@@ -0,0 +1,11 @@
+struct Kube {
+    home: String,
+    public_key: String,
+    private_key: String,
+}
+
+pub let main = Kube {
+    home: '/home/bob',
+    public_key: '/home/bob/.ssh/id_ed25519.pub',
+    privte_key: '/home/bob/.ssh/id_ed25519',
+};
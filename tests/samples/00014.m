@@ -0,0 +1,10 @@
+struct Deployment {
+    metadata.name: String,
+    metadata.namespace: String,
+}
+
+pub let main = Deployment {
+    metadata.name: 'api',
+    metadata.namespace: 'default',
+    metadata.name: 'web',
+};
@@ -0,0 +1,7 @@
+struct Kube {
+    home: String,
+}
+
+let base = Kube { home: '/home/bob' };
+
+pub let main = Kube { ..base, shell: '/bin/zsh' };
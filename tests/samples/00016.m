@@ -0,0 +1,36 @@
+enum Protocol {
+    Tcp,
+    Udp,
+}
+
+enum Source {
+    Git(String),
+    Path(String),
+    Inline,
+}
+
+struct Port {
+    number: Number,
+    protocol: Protocol = Protocol.Tcp,
+}
+
+struct Build {
+    source: Source,
+    kind: String,
+    ports: Array<Port>,
+}
+
+let repo = Source.Git('https://example.com/app.git');
+
+pub let main = Build {
+    source: repo,
+    kind: match repo {
+        Git(url) => 'git: ' + url,
+        Path(path) => 'local: ' + path,
+        _ => 'inline',
+    },
+    ports: [
+        Port { number: 80 },
+        Port { number: 53, protocol: Protocol.Udp },
+    ],
+};
@@ -0,0 +1,11 @@
+enum Protocol {
+    Tcp,
+    Udp,
+    Sctp,
+}
+
+let protocol = Protocol.Udp;
+
+pub let main = match protocol {
+    Tcp => 6,
+};
@@ -0,0 +1,14 @@
+enum Protocol {
+    Tcp,
+    Udp,
+}
+
+struct Port {
+    number: Number,
+    protocol: Protocol,
+}
+
+pub let main = Port {
+    number: 80,
+    protocol: Protocol.Udpp,
+};
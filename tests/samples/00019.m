@@ -0,0 +1,34 @@
+struct Named<T> {
+    name: String,
+    value: T,
+}
+
+struct Versioned<T> {
+    version: Number = 1,
+    item: T,
+}
+
+struct Pair<A, B> {
+    first: A,
+    second: B,
+}
+
+struct Release {
+    port: Pair<String, Number>,
+    image: Versioned<Named<String>>,
+    replicas: Named<Number>,
+}
+
+fn named<T>(name: String, value: T): Named<T> {
+    return Named { name: name, value: value };
+}
+
+fn latest<T>(item: T): Versioned<T> {
+    return Versioned { version: 2, item: item };
+}
+
+pub let main = Release {
+    port: Pair { first: 'http', second: 80 },
+    image: latest(named('image', 'nginx:1.25')),
+    replicas: named('replicas', 3),
+};
@@ -0,0 +1,16 @@
+struct Named<T> {
+    name: String,
+    value: T,
+}
+
+struct Release {
+    image: Named<String>,
+}
+
+fn named<T>(name: String, value: T): Named<T> {
+    return Named { name: name, value: value };
+}
+
+pub let main = Release {
+    image: named('image', 3),
+};
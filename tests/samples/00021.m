@@ -0,0 +1,19 @@
+struct Deployment {
+    name: String,
+    labels: Map<String, String>,
+    env: Map<String, String> = {},
+    selectors: Array<String>,
+    owner: String?,
+    ports: Array<Number>,
+}
+
+let team = { 'team': 'platform', 'tier': 'backend' };
+let labels = team // { 'app': 'api', 'tier': 'frontend' };
+
+pub let main = Deployment {
+    name: 'api',
+    labels: labels,
+    selectors: for key, value in labels { key + '=' + value },
+    owner: labels['team'],
+    ports: for i, port in [80, 443] { port + i },
+};
@@ -0,0 +1,7 @@
+struct Service {
+    annotations: Map<String, String>,
+}
+
+pub let main = Service {
+    annotations: { 'replicas': 3 },
+};
@@ -0,0 +1,4 @@
+let defaults = { 'web': { 'cpu': '100m', 'memory': '128Mi' } };
+let overrides = { 'web': { 'memory': '256Mi' }, 'worker': { 'cpu': '500m' } };
+
+pub let main = defaults /\ overrides;
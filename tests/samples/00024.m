@@ -0,0 +1,27 @@
+type Port = String | Number;
+type Name = String;
+
+struct ServicePort {
+    name: Name,
+    port: Number,
+    targetPort: Port,
+}
+
+let web: Port = 'web';
+let metrics: Port = 9090;
+
+pub let main = [
+    ServicePort {
+        name: 'http',
+        port: 80,
+        targetPort: web,
+    },
+    ServicePort {
+        name: if metrics is String { metrics } else { 'metrics' },
+        port: match metrics {
+            n: Number => n,
+            _: String => 9000,
+        },
+        targetPort: metrics,
+    },
+];
@@ -0,0 +1,7 @@
+type Port = String | Number;
+
+let port: Port = 80;
+
+pub let main = match port {
+    n: Number => n + 1,
+};
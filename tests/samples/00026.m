@@ -0,0 +1,18 @@
+struct User {
+    name: String,
+    age: Number,
+}
+
+fn makeUser(name: String, age: Number): User {
+    return User { name: name, age: age };
+}
+
+fn increment(x: Number): Number {
+    return x + 1;
+}
+
+fn twice(f: Fn(Number) -> Number, x: Number): Number {
+    return f(f(x));
+}
+
+pub let main = makeUser('ada', twice(increment, 34));
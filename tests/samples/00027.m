@@ -0,0 +1,10 @@
+struct User {
+    name: String,
+    age: Number,
+}
+
+fn makeUser(name: String, age: Number): User {
+    return User { name: name, age: age };
+}
+
+pub let main = makeUser(1, 2, 3);
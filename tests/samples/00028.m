@@ -0,0 +1,5 @@
+fn port(name: String): Number {
+    let fallback = 8080;
+}
+
+pub let main = port('http');
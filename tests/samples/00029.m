@@ -0,0 +1,24 @@
+struct Container {
+    name: String,
+    image: String,
+    port: Number,
+}
+
+fn each<T, U>(items: Array<T>, f: Fn(T) -> U): Array<U> {
+    return for item in items { f(item) };
+}
+
+let registry = 'ghcr.io/acme/';
+
+fn container(name: String, port: Number): Container {
+    return Container { name: name, image: registry + name, port: port };
+}
+
+fn prefixer(prefix: String): Fn(String) -> String {
+    return (name) => prefix + name;
+}
+
+let tag = prefixer('svc-');
+let offset = (port: Number) => port + 8000;
+
+pub let main = each(['api', 'worker'], (name) => container(tag(name), offset(80)));
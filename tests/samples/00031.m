@@ -0,0 +1,28 @@
+import Array from 'Array';
+import upper from 'String';
+
+struct Summary {
+    names: Array<String>,
+    hosts: Array<String>,
+    env: String,
+    workers: Array<String>,
+    ports: Array<Number>,
+    tags: Array<String>,
+    count: Number,
+    timeout: Number?,
+    invalid: Number?,
+}
+
+let names = map(split('api, worker ,cron-worker', ','), (n) => trim(n));
+
+pub let main = Summary {
+    names: sort(names),
+    hosts: map(names, (n) => lower(replace(n, '-', '.')) + '.svc'),
+    env: upper(join(names, '_')),
+    workers: filter(names, (n) => startsWith(n, 'cron')),
+    ports: concat(range(8080, 8083), flatten([[9090], [9091]])),
+    tags: unique(['a', 'b', 'a', toString(len(names))]),
+    count: len(names),
+    timeout: parseNumber(' 30 '),
+    invalid: parseNumber('soon'),
+};
@@ -0,0 +1,3 @@
+import frobnicate from 'String';
+
+pub let main = 1;
@@ -0,0 +1,26 @@
+import Encoding from 'Encoding';
+import sha256 from 'Hash';
+
+struct Secret {
+    user: String,
+    password: String,
+    decoded: String?,
+    invalid: String?,
+    checksum: String,
+    hex: String,
+    query: String,
+    id: String?,
+}
+
+let config = 'replicas: 3';
+
+pub let main = Secret {
+    user: base64Encode('admin'),
+    password: base64Encode('s3cr3t!?'),
+    decoded: base64Decode('aGVsbG8gd29ybGQ='),
+    invalid: base64Decode('not base64!'),
+    checksum: sha256(config),
+    hex: hex('mia'),
+    query: urlEncode('a b&c=d/é'),
+    id: uuid5('6ba7b810-9dad-11d1-80b4-00c04fd430c8', 'www.example.com'),
+};
@@ -0,0 +1,27 @@
+struct Service {
+    name: String matching '^[a-z][a-z0-9-]*$',
+    port: Int where 1 <= it && it <= 65535 = 8080,
+    replicas: Number where it > 0 && it != 13 = 1,
+    limits.cpu: Number? where it <= 4,
+}
+
+let replicas = 3;
+assert replicas < 10, 'too many replicas';
+assert !(replicas == 0) || false;
+
+let web = Service { name: 'web-1', replicas: replicas, limits.cpu: 2 };
+let api = Service { ..web, name: 'api' };
+
+struct Output {
+    web: Service,
+    api: Service,
+    checks: Array<Bool>,
+    valid: Bool,
+}
+
+pub let main = Output {
+    web: web,
+    api: api,
+    checks: [1 < 2, 'a' >= 'b', -1 <= 0, web == web, web != api],
+    valid: matches('release-42', '^[a-z]+-\d{2}$'),
+};
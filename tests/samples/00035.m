@@ -0,0 +1,11 @@
+struct Service {
+    name: String matching '^[a-z][a-z0-9-]*$',
+    port: Int where 1 <= it && it <= 65535 = 8080,
+}
+
+let ok = Service { name: 'web' };
+
+pub let main = Service {
+    name: 'api',
+    port: 70000,
+};
@@ -0,0 +1,5 @@
+struct Service {
+    name: String matching '^[a-z][a-z0-9-]*$',
+}
+
+pub let main = Service { name: 'Web Server' };
@@ -0,0 +1,5 @@
+let ports = [80, 443];
+
+assert len(ports) == 3, 'expected three ports';
+
+pub let main = ports;
@@ -0,0 +1,20 @@
+import Deployment from '00038.json';
+import Container from '00038.json';
+import PullPolicy from '00038.json';
+import ContainerPortsItem from '00038.json';
+import Env1 from '00038.json';
+
+let web = Container {
+    image: 'nginx:1.27',
+    pullPolicy: PullPolicy.IfNotPresent,
+    ports: [ContainerPortsItem { containerPort: 8080 }],
+    env: [Env1 { name: 'MODE', value: 'production' }],
+};
+
+pub let main = Deployment {
+    name: 'web',
+    labels: { 'app': 'web' },
+    containers: [web],
+    'runs-on': 'ubuntu-latest',
+    '$comment': 'rendered by mia',
+};
@@ -0,0 +1,8 @@
+import Deployment from '00038.json';
+import Container from '00038.json';
+
+pub let main = Deployment {
+    name: 'web',
+    replicas: 0,
+    containers: [Container { image: 'nginx' }],
+};
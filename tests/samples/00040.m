@@ -0,0 +1,3 @@
+import Service from '00038.json';
+
+pub let main = 1;
@@ -0,0 +1,41 @@
+pub enum DeploymentStrategy {
+    Recreate,
+    RollingUpdate,
+}
+
+pub enum PullPolicy {
+    Always,
+    IfNotPresent,
+    Never,
+}
+
+pub struct ContainerPortsItem {
+    containerPort: Int where it >= 1 && it <= 65535,
+    protocol: String matching '^(TCP|UDP|sctp)$' = 'TCP',
+}
+
+pub struct Env1 {
+    name: String,
+    value: String,
+}
+
+pub type Env = Env1 | String;
+
+pub struct Container {
+    image: String,
+    pullPolicy: PullPolicy?,
+    ports: Array<ContainerPortsItem>?,
+    env: Array<Env>?,
+}
+
+pub struct Deployment {
+    name: String where matches(it, '^[a-z][a-z0-9-]*$') && len(it) <= 63,
+    replicas: Int where it >= 1 = 1,
+    strategy: DeploymentStrategy = DeploymentStrategy.RollingUpdate,
+    labels: Map<String, String>?,
+    containers: Array<Container> where len(it) >= 1,
+    'runs-on': String?,
+    '$comment': String?,
+}
+
+pub type Unused = Number;
@@ -0,0 +1,37 @@
+enum Protocol {
+    Tcp,
+    Udp,
+}
+
+type Port = String | Number;
+
+struct Named<T> {
+    name: String,
+    value: T,
+}
+
+struct Container {
+    image: String,
+    containerPort: Port,
+    protocol: Protocol = Protocol.Tcp,
+    type: String = 'service',
+    args: Array<String>,
+    env: Map<String, String>,
+    limits.cpu: Number?,
+    limits.memory: String?,
+    labels: Array<Named<String>>,
+    render: Fn(String) -> String,
+}
+
+fn identity(s: String): String {
+    return s;
+}
+
+pub let main = Container {
+    image: 'nginx',
+    containerPort: 80,
+    args: ['-g'],
+    env: { 'MODE': 'prod' },
+    labels: [Named { name: 'app', value: 'web' }],
+    render: identity,
+};
@@ -0,0 +1,40 @@
+// Generated by mia from tests/samples/00044.m, do not edit.
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(untagged)]
+pub enum Port {
+    String(String),
+    Number(f64),
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct Named<T> {
+    pub name: String,
+    pub value: T,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct ContainerLimits {
+    pub cpu: Option<f64>,
+    pub memory: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct Container {
+    pub image: String,
+    #[serde(rename = "containerPort")]
+    pub container_port: Port,
+    pub protocol: Protocol,
+    pub r#type: String,
+    pub args: Vec<String>,
+    pub env: std::collections::BTreeMap<String, String>,
+    pub limits: ContainerLimits,
+    pub labels: Vec<Named<String>>,
+    // 'render' is skipped, 'Fn(String) -> String' is not data.
+}
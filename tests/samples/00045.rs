@@ -0,0 +1,29 @@
+// Generated by mia from tests/samples/00016.m, do not edit.
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(tag = "tag", content = "value")]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(tag = "tag", content = "value")]
+pub enum Source {
+    Git(String),
+    Path(String),
+    Inline,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct Port {
+    pub number: f64,
+    pub protocol: Protocol,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct Build {
+    pub source: Source,
+    pub kind: String,
+    pub ports: Vec<Port>,
+}
@@ -0,0 +1,27 @@
+// Generated by mia from tests/samples/00044.m, do not edit.
+
+export type Protocol = "Tcp" | "Udp";
+
+export type Port = string | number;
+
+export interface Named<T> {
+    name: string;
+    value: T;
+}
+
+export interface Container {
+    image: string;
+    containerPort: Port;
+    protocol: Protocol;
+    type: string;
+    args: string[];
+    env: Record<string, string>;
+    limits: {
+        cpu: number | null;
+        memory: string | null;
+    };
+    labels: Named<string>[];
+    // 'render' is skipped, 'Fn(String) -> String' is not data.
+}
+
+export declare const main: Container;
@@ -0,0 +1,24 @@
+// Generated by mia from tests/samples/00047.m, do not edit.
+
+export type Source = string | "Inline";
+
+export interface Pair<A, B> {
+    left: A;
+    right: B;
+}
+
+export declare const regions: string[] | null;
+
+export declare const weights: Record<string, number>;
+
+export declare const source: Source;
+
+export declare const pair: {
+    left: string;
+    right: number;
+};
+
+export declare const main: {
+    left: Source;
+    right: string[] | null;
+};
@@ -0,0 +1,16 @@
+enum Source {
+    Git(String),
+    Path(String),
+    Inline,
+}
+
+struct Pair<A, B> {
+    left: A,
+    right: B,
+}
+
+pub let regions: Array<String>? = ['eu-west-1'];
+pub let weights = { 'eu': 1, 'us': 2 };
+pub let source = Source.Path('./app');
+pub let pair = Pair { left: 'a', right: 1 };
+pub let main = Pair { left: source, right: regions };
@@ -0,0 +1,14 @@
+struct Service {
+    name: String,
+    port: Number where it < 65536,
+}
+
+fn service(name: String, port: Number): Service {
+    return Service { name: name, port: port };
+}
+
+fn web(port: Number): Service {
+    return service('web', port);
+}
+
+pub let main = web(70000);
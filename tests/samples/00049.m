@@ -0,0 +1,7 @@
+let patterns = ['^[a-z]+$', '(web'];
+
+fn valid(pattern: String): Bool {
+    return matches('web', pattern);
+}
+
+pub let main = map(patterns, valid);
@@ -0,0 +1,4 @@
+let base = 40;
+
+pub let main = base +
+    2;
@@ -0,0 +1,10 @@
+enum Protocol { Tcp, Udp }
+
+let twice = (x: Number) => x * 2;
+
+pub let main = for port in [80, 443] {
+    match Protocol.Tcp {
+        Tcp => twice(port),
+        _ => port,
+    }
+};
@@ -0,0 +1,10 @@
+struct Port {
+    name: String,
+    number: Number,
+}
+
+pub let main = [
+    Port { name: 'http', number: 80 },
+    Port { name: 'http', number: 8080 },
+    Port { name: 'metrics', number: 8080 },
+];
@@ -0,0 +1,31 @@
+struct Defaults {
+    replicas: Number,
+    resources.cpu: String,
+    resources.memory: String,
+}
+
+struct Overrides {
+    resources.memory: String,
+}
+
+struct Environments {
+    dev: Array<Defaults>,
+    prod: Defaults,
+}
+
+let team = Defaults {
+    replicas: 2,
+    resources.cpu: '500m',
+    resources.memory: '256Mi',
+};
+
+let prod = Overrides {
+    resources.memory: '1Gi',
+};
+
+pub let main = Environments {
+    dev: for i in range(0, 2) {
+        team
+    },
+    prod: team /\ prod,
+};
@@ -0,0 +1,20 @@
+struct Meta {
+    name: String,
+    ns: String,
+}
+
+struct K {
+    kind: String,
+    meta: Meta,
+    spec.replicas: Number,
+    spec.image: String,
+}
+
+let base = K {
+    kind: 'Deployment',
+    meta: Meta { name: 'web', ns: 'prod' },
+    spec.replicas: 2,
+    spec.image: 'nginx',
+};
+
+pub let main = K { ..base, meta.name: 'api', spec.replicas: 3 };
@@ -0,0 +1,5 @@
+fn port(): Number {
+    return 8080;
+}
+
+return port();
@@ -0,0 +1,30 @@
+enum Nat {
+    Zero,
+    Succ(Nat),
+}
+
+fn toNumber(n: Nat): Number {
+    return match n {
+        Zero => 0,
+        Succ(m) => 1 + toNumber(m),
+    };
+}
+
+fn names(n: Nat, prefix: String): Array<String> {
+    return match n {
+        Zero => [],
+        Succ(m) => concat(names(m, prefix), [prefix + toString(toNumber(m))]),
+    };
+}
+
+let three = Nat.Succ(Nat.Succ(Nat.Succ(Nat.Zero)));
+
+struct Pods {
+    replicas: Number,
+    names: Array<String>,
+}
+
+pub let main = Pods {
+    replicas: toNumber(three),
+    names: names(three, 'web-'),
+};
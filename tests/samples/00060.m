@@ -0,0 +1,17 @@
+fn trim(x: Number): Number {
+    return x * 2;
+}
+
+let map = {'a': 1, 'b': 2};
+
+struct Out {
+    trimmed: Number,
+    a: Number?,
+    name: String,
+}
+
+pub let main = Out {
+    trimmed: trim(3),
+    a: map['a'],
+    name: upper('web'),
+};
@@ -0,0 +1,10 @@
+struct Service {
+    name: String,
+    port: Int where 1 <= it && it <= 65535 = 8080,
+    replicas: Int? = 1,
+}
+
+pub let main = Service {
+    name: 'web',
+    port: 80.5,
+};
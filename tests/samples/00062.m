@@ -0,0 +1,3 @@
+import Container from '00038.json';
+
+pub let main = Container { image: 'nginx', pullPolicy: 'Always' };
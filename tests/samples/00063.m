@@ -0,0 +1,7 @@
+struct Job {
+    'runs-on': String,
+    'timeout-minutes': Int = 10,
+    steps.'continue-on-error': Bool = false,
+}
+
+pub let main = Job { 'runs-on': 'ubuntu-latest', steps.'continue-on-error': true };
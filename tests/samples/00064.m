@@ -0,0 +1,6 @@
+struct Service {
+    name: String where matches(it, '^[a-z]') && matches(it, '[0-9]$') && len(it) == 8,
+    replicas: Number where it >= -5 && it * 2 < 100 && (it < 3 || it > 10),
+}
+
+pub let main = Service { name: 'abcdefg1', replicas: 1 };
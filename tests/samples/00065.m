@@ -0,0 +1 @@
+pub let main = { 'say "hi"': 'C:\temp', 'digits': '^\d+$' };
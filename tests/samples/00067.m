@@ -0,0 +1,8 @@
+struct S {
+    meta: Map<String, String>,
+}
+
+pub let main = S {
+    meta: {},
+    meta.ns: 'b',
+};
@@ -0,0 +1,5 @@
+fn f(n: Number): Number {
+    return f(n);
+}
+
+pub let main = f(1);
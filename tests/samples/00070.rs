@@ -0,0 +1,51 @@
+// Generated by mia from tests/samples/00016.m, do not edit.
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(from = "SourceRepr")]
+pub enum Source {
+    Git(String),
+    Path(String),
+    Inline,
+}
+
+#[derive(serde::Deserialize)]
+enum SourceUnit {
+    Inline,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum SourceRepr {
+    Unit(SourceUnit),
+    Git(String),
+    Path(String),
+}
+
+impl From<SourceRepr> for Source {
+    fn from(repr: SourceRepr) -> Self {
+        match repr {
+            SourceRepr::Unit(SourceUnit::Inline) => Source::Inline,
+            SourceRepr::Git(value) => Source::Git(value),
+            SourceRepr::Path(value) => Source::Path(value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct Port {
+    pub number: f64,
+    pub protocol: Protocol,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct Build {
+    pub source: Source,
+    pub kind: String,
+    pub ports: Vec<Port>,
+}
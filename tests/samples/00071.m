@@ -0,0 +1,6 @@
+pub struct Schema00071 {
+    # The pattern "^[a-z" of 'a' is skipped, it cannot be expressed.
+    a: String?,
+    # The pattern "it's" of 'b' is skipped, it cannot be expressed.
+    b: String? where len(it) >= 2,
+}